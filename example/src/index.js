@@ -103,7 +103,7 @@ const newBlock = index => {
     let verifier = new biscuit.Verifier()
     verifier.addResource(resourceI.value);
     verifier.addOperation(operationI.value);
-    verifier.setTime((new Date().getTime() / 1000).toFixed(0));
+    verifier.setTimeNow();
 
     let rule = biscuit.rule(
       "check_right",
@@ -127,7 +127,7 @@ const newBlock = index => {
       ]
     );
 
-    verifier.addCaveat(rule)
+    verifier.addCaveat(biscuit.Caveat.fromRule(rule))
     //verifier.addBlockCaveat(rule)
 
     let decoded = fromHex(privkeyI.value);
@@ -202,11 +202,11 @@ const newBlock = index => {
     let operation = attenuationData.value;
 
     let block = token.createBlock();
-    block.addCaveat(biscuit.rule(
+    block.addCaveat(biscuit.Caveat.fromRule(biscuit.rule(
       "operation_check",
       [{ symbol: operation }],
       [{ name: "operation", ids: [{ symbol: "ambient" }, { symbol: operation }] }]
-    ));
+    )));
 
     let keypair2 = new biscuit.KeyPair()
     let token2 = token.append(keypair2, block);
@@ -223,7 +223,7 @@ const newBlock = index => {
     let resourcePrefix = attenuationData.value;
 
     let block = token.createBlock();
-    block.addCaveat(biscuit.constrained_rule(
+    block.addCaveat(biscuit.Caveat.fromRule(biscuit.constrained_rule(
       "resource_check",
       [{ variable: 0 }],
       [
@@ -233,7 +233,7 @@ const newBlock = index => {
         },
       ],
       [{id: 0, kind: "string", operation: "prefix", data: resourcePrefix}]
-    ));
+    )));
 
     let keypair2 = new biscuit.KeyPair()
     let token2 = token.append(keypair2, block);
@@ -248,11 +248,11 @@ const newBlock = index => {
 
     let attenuationData = document.getElementById("attenuation_expiration_data");
     let seconds = parseInt(attenuationData.value);
-    let expirationDate = parseInt(((new Date((new Date()).getTime() + seconds * 1000)) / 1000).toFixed(0));
+    let expirationDate = new Date((new Date()).getTime() + seconds * 1000);
     console.log("expires at "+expirationDate);
 
     let block = token.createBlock();
-    block.addCaveat(biscuit.constrained_rule(
+    block.addCaveat(biscuit.Caveat.fromRule(biscuit.constrained_rule(
       "expiration_check",
       [{ variable: 0 }],
       [
@@ -262,7 +262,7 @@ const newBlock = index => {
         },
       ],
       [{id: 0, kind: "date", operation: "<=", data: expirationDate}]
-    ));
+    )));
 
     let keypair2 = new biscuit.KeyPair()
     let token2 = token.append(keypair2, block);
@@ -3,81 +3,451 @@ use biscuit::token;
 use biscuit::token::default_symbol_table;
 use biscuit::datalog::{self, SymbolTable};
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
 use rand::rngs::OsRng;
 use serde::{Serialize, Deserialize};
 use std::default::Default;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::time::{SystemTime, Duration};
 
 use super::Biscuit;
+use crate::error::Error;
 
-#[wasm_bindgen]
+#[wasm_bindgen(inline_js = "export function is_bigint(v) { return typeof v === 'bigint'; }")]
 extern "C" {
-    // Use `js_namespace` here to bind `console.log(..)` instead of just
-    // `log(..)`
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
-}
-
-macro_rules! console_log {
-    // Note that this is using the `log` function imported above during
-    // `bare_bones`
-    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
+    // `dyn_ref::<BigInt>` checks `instanceof BigInt`, which is false for
+    // BigInt primitives like `123n`
+    fn is_bigint(value: &JsValue) -> bool;
 }
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct Atom {
+  #[serde(default, skip_serializing_if = "Option::is_none",
+    serialize_with = "json_integer::serialize_option", deserialize_with = "json_integer::deserialize_option")]
   pub(crate) integer: Option<i64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub(crate) string: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub(crate) symbol: Option<String>,
+  /// Unix timestamp in seconds, serialized in RFC 3339
+  #[serde(default, skip_serializing_if = "Option::is_none",
+    serialize_with = "json_date::serialize_option", deserialize_with = "json_date::deserialize_option")]
   pub(crate) date: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub(crate) variable: Option<String>,
 }
 
 impl Atom {
-  pub fn into_atom(self) -> builder::Atom {
-    let Atom { integer, string, symbol, date, variable } = self;
-
-    if let Some(i) = integer {
-      builder::int(i)
-    } else if let Some(s) = string {
-      builder::string(&s)
-    } else if let Some(s) = symbol {
-      builder::symbol(&s)
-    } else if let Some(i) = date {
-      builder::Atom::Date(i)
-    } else if let Some(i) = variable {
-      builder::variable(&i)
-    } else {
-      panic!("invalid atom: {:?}", Atom { integer, string, symbol, date, variable });
+  /// converts to a builder atom, failing if zero or more than one field is set
+  pub fn try_into_atom(self) -> Result<builder::Atom, Error> {
+    match self {
+      Atom { integer: Some(i), string: None, symbol: None, date: None, variable: None } => Ok(builder::int(i)),
+      Atom { integer: None, string: Some(s), symbol: None, date: None, variable: None } => Ok(builder::string(&s)),
+      Atom { integer: None, string: None, symbol: Some(s), date: None, variable: None } => Ok(builder::symbol(&s)),
+      Atom { integer: None, string: None, symbol: None, date: Some(i), variable: None } => Ok(builder::Atom::Date(i)),
+      Atom { integer: None, string: None, symbol: None, date: None, variable: Some(s) } => Ok(builder::variable(&s)),
+      a => Err(Error::InvalidAtom(format!("an atom must set exactly one field: {:?}", a))),
+    }
+  }
+
+}
+
+impl From<builder::Atom> for Atom {
+  fn from(a: builder::Atom) -> Self {
+    match a {
+      builder::Atom::Integer(i) => Atom { integer: Some(i), ..Default::default() },
+      builder::Atom::Str(s) => Atom { string: Some(s), ..Default::default() },
+      builder::Atom::Symbol(s) => Atom { symbol: Some(s), ..Default::default() },
+      builder::Atom::Date(i) => Atom { date: Some(i), ..Default::default() },
+      builder::Atom::Variable(s) => Atom { variable: Some(s), ..Default::default() },
+    }
+  }
+}
+
+/// reads an integer from a BigInt, from a number if it is a safe integer,
+/// or from a decimal string as returned by `toJSON`
+pub(crate) fn js_to_integer(value: &JsValue) -> Result<i64, Error> {
+  if let Some(f) = value.as_f64() {
+    safe_integer(f)
+  } else if is_bigint(value) {
+    let s: String = value.unchecked_ref::<js_sys::BigInt>().to_string(10)
+      .map_err(|_| Error::InvalidAtom("invalid BigInt".to_string()))?
+      .into();
+    parse_integer(&s)
+  } else if let Some(s) = value.as_string() {
+    parse_integer(&s)
+  } else {
+    Err(Error::InvalidAtom(format!("expected an integer: {:?}", value)))
+  }
+}
+
+fn safe_integer(f: f64) -> Result<i64, Error> {
+  if f.fract() != 0.0 || !f.is_finite() {
+    Err(Error::InvalidAtom(format!("{} is not an integer", f)))
+  } else if f.abs() > MAX_SAFE_INTEGER {
+    Err(Error::InvalidAtom(format!("{} is not a safe integer, use a BigInt or a string", f)))
+  } else {
+    Ok(f as i64)
+  }
+}
+
+fn parse_integer(s: &str) -> Result<i64, Error> {
+  s.parse::<i64>()
+    .map_err(|_| Error::InvalidAtom(format!("{} is not an integer in the 64 bits range", s)))
+}
+
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// serde representation of integers: JSON numbers lose precision above
+/// 2^53, so integers are written as decimal strings, and read from decimal
+/// strings or from safe integer numbers
+pub(crate) mod json_integer {
+  use serde::{de, Deserialize, Deserializer, Serializer};
+  use std::collections::HashSet;
+  use std::fmt;
+
+  pub fn serialize<S: Serializer>(i: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(i)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    deserializer.deserialize_any(IntegerVisitor { strings: true })
+  }
+
+  /// only accepts safe integer numbers, for untagged enums where strings
+  /// are matched by another variant
+  pub fn deserialize_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    deserializer.deserialize_any(IntegerVisitor { strings: false })
+  }
+
+  pub fn serialize_option<S: Serializer>(i: &Option<i64>, serializer: S) -> Result<S::Ok, S::Error> {
+    match i {
+      Some(i) => serialize(i, serializer),
+      None => serializer.serialize_none(),
     }
   }
+
+  pub fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i64>, D::Error> {
+    let i: Option<Integer> = Deserialize::deserialize(deserializer)?;
+    Ok(i.map(|i| i.0))
+  }
+
+  pub fn serialize_set<S: Serializer>(set: &HashSet<i64>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut v: Vec<i64> = set.iter().cloned().collect();
+    v.sort();
+    serializer.collect_seq(v.iter().map(|i| i.to_string()))
+  }
+
+  pub fn deserialize_number_set<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashSet<i64>, D::Error> {
+    let v: Vec<Number> = Deserialize::deserialize(deserializer)?;
+    Ok(v.into_iter().map(|i| i.0).collect())
+  }
+
+  pub fn serialize_vec<S: Serializer>(v: &[i64], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(v.iter().map(|i| i.to_string()))
+  }
+
+  pub fn deserialize_vec<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i64>, D::Error> {
+    let v: Vec<Integer> = Deserialize::deserialize(deserializer)?;
+    Ok(v.into_iter().map(|i| i.0).collect())
+  }
+
+  struct Integer(i64);
+
+  impl<'de> Deserialize<'de> for Integer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      deserialize(deserializer).map(Integer)
+    }
+  }
+
+  struct IntegerVisitor {
+    strings: bool,
+  }
+
+  impl<'de> de::Visitor<'de> for IntegerVisitor {
+    type Value = i64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      if self.strings {
+        f.write_str("a decimal string or a safe integer")
+      } else {
+        f.write_str("a safe integer")
+      }
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<i64, E> {
+      super::safe_integer(v as f64).map_err(|e| E::custom(format!("{:?}", e)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<i64, E> {
+      super::safe_integer(v as f64).map_err(|e| E::custom(format!("{:?}", e)))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<i64, E> {
+      super::safe_integer(v).map_err(|e| E::custom(format!("{:?}", e)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<i64, E> {
+      if !self.strings {
+        return Err(E::invalid_type(de::Unexpected::Str(v), &self));
+      }
+      super::parse_integer(v).map_err(|e| E::custom(format!("{:?}", e)))
+    }
+  }
+
+  struct Number(i64);
+
+  impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      deserialize_number(deserializer).map(Number)
+    }
+  }
+}
+
+/// serde representation of dates: RFC 3339 strings, read from RFC 3339
+/// strings or from numbers of milliseconds, as returned by `Date.getTime()`
+pub(crate) mod json_date {
+  use serde::{de, ser, Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(seconds: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    let s = super::date_to_rfc3339(*seconds)
+      .ok_or_else(|| ser::Error::custom(format!("date out of range: {}", seconds)))?;
+    serializer.serialize_str(&s)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    match Date::deserialize(deserializer)? {
+      Date::Text(s) => super::parse_date(&s),
+      Date::Milliseconds(ms) => super::milliseconds_to_date(ms),
+    }.map_err(|e| de::Error::custom(format!("{:?}", e)))
+  }
+
+  pub fn serialize_option<S: Serializer>(d: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+    match d {
+      Some(d) => serialize(d, serializer),
+      None => serializer.serialize_none(),
+    }
+  }
+
+  pub fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let d: Option<Seconds> = Deserialize::deserialize(deserializer)?;
+    Ok(d.map(|d| d.0))
+  }
+
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Date {
+    Text(String),
+    Milliseconds(i64),
+  }
+
+  struct Seconds(u64);
+
+  impl<'de> Deserialize<'de> for Seconds {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      deserialize(deserializer).map(Seconds)
+    }
+  }
+}
+
+fn parse_date(s: &str) -> Result<u64, Error> {
+  let seconds = chrono::DateTime::parse_from_rfc3339(s)
+    .map_err(|e| Error::InvalidAtom(format!("invalid date {}: {}", s, e)))?
+    .timestamp();
+
+  if seconds < 0 {
+    Err(Error::InvalidAtom(format!("dates before 1970 are not supported: {}", s)))
+  } else {
+    Ok(seconds as u64)
+  }
+}
+
+/// dates are stored with a precision of one second, milliseconds are truncated
+fn milliseconds_to_date(ms: i64) -> Result<u64, Error> {
+  if ms < 0 {
+    Err(Error::InvalidAtom(format!("dates before 1970 are not supported: {}", ms)))
+  } else {
+    Ok((ms / 1000) as u64)
+  }
+}
+
+pub(crate) fn date_to_rfc3339(seconds: u64) -> Option<String> {
+  use chrono::TimeZone;
+
+  if seconds > i64::MAX as u64 {
+    return None;
+  }
+  chrono::Utc.timestamp_opt(seconds as i64, 0).single()
+    .map(|d| d.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
 }
 
+/// reads a date as Unix seconds, from a `Date` object, an RFC 3339 string
+/// or a number of milliseconds since the epoch, as returned by `Date.getTime()`
+/// and `Date.now()`. Milliseconds are truncated
+pub(crate) fn js_to_date(value: &JsValue) -> Result<u64, Error> {
+  if let Some(d) = value.dyn_ref::<js_sys::Date>() {
+    let t = d.get_time();
+    if t.is_nan() {
+      return Err(Error::InvalidAtom("invalid date".to_string()));
+    }
+    milliseconds_to_date(t as i64)
+  } else if let Some(s) = value.as_string() {
+    parse_date(&s)
+  } else {
+    milliseconds_to_date(js_to_integer(value)?)
+  }
+}
+
+/// dates are returned as `Date` objects
+pub(crate) fn date_to_js(seconds: u64) -> JsValue {
+  js_sys::Date::new(&JsValue::from_f64(seconds as f64 * 1000.0)).into()
+}
+
+/// converts a JS value to an atom:
+/// - a number or a BigInt is an integer
+/// - a `Date` object is a date
+/// - a string is a string
+/// - other objects use the `Atom` format (`{ symbol: "read" }`)
+pub(crate) fn js_to_atom(value: &JsValue) -> Result<builder::Atom, Error> {
+  if let Some(s) = value.as_string() {
+    Ok(builder::string(&s))
+  } else if value.as_f64().is_some() || is_bigint(value) {
+    js_to_integer(value).map(builder::int)
+  } else if value.dyn_ref::<js_sys::Date>().is_some() {
+    js_to_date(value).map(builder::Atom::Date)
+  } else if value.is_object() {
+    js_object_to_atom(value)
+  } else {
+    Err(Error::InvalidAtom(format!("unsupported value: {:?}", value)))
+  }
+}
+
+/// reads an object in the `Atom` format. This does not go through serde,
+/// which cannot handle BigInt values
+fn js_object_to_atom(value: &JsValue) -> Result<builder::Atom, Error> {
+  let field = |name: &str| {
+    js_sys::Reflect::get(value, &JsValue::from_str(name))
+      .ok()
+      .filter(|v| !v.is_undefined() && !v.is_null())
+  };
+  let text = |v: JsValue| {
+    v.as_string().ok_or_else(|| Error::InvalidAtom(format!("expected a string: {:?}", v)))
+  };
+
+  let mut atoms = vec![];
+  if let Some(i) = field("integer") {
+    atoms.push(builder::int(js_to_integer(&i)?));
+  }
+  if let Some(s) = field("string") {
+    atoms.push(builder::string(&text(s)?));
+  }
+  if let Some(s) = field("symbol") {
+    atoms.push(builder::symbol(&text(s)?));
+  }
+  if let Some(d) = field("date") {
+    atoms.push(builder::Atom::Date(js_to_date(&d)?));
+  }
+  if let Some(s) = field("variable") {
+    atoms.push(builder::variable(&text(s)?));
+  }
+
+  if atoms.len() == 1 {
+    Ok(atoms.remove(0))
+  } else {
+    Err(Error::InvalidAtom(format!("an atom must set exactly one field: {:?}", value)))
+  }
+}
+
+/// converts an atom to the `Atom` object format, with integers as decimal
+/// strings so that the result can be given to `JSON.stringify`
+/// converts an integer to a BigInt, which holds all the values of an i64
+pub(crate) fn integer_to_js(i: i64) -> JsValue {
+  js_sys::BigInt::new(&JsValue::from_str(&i.to_string())).into()
+}
+
+pub(crate) fn atom_to_js(atom: &builder::Atom) -> JsValue {
+  let (field, value) = match atom {
+    builder::Atom::Integer(i) => ("integer", integer_to_js(*i)),
+    builder::Atom::Str(s) => ("string", JsValue::from_str(s)),
+    builder::Atom::Symbol(s) => ("symbol", JsValue::from_str(s)),
+    builder::Atom::Date(d) => ("date", date_to_js(*d)),
+    builder::Atom::Variable(s) => ("variable", JsValue::from_str(s)),
+  };
+
+  let object = js_sys::Object::new();
+  js_sys::Reflect::set(&object, &JsValue::from_str(field), &value).unwrap();
+  object.into()
+}
+
+pub(crate) fn js_to_atoms(values: &JsValue) -> Result<Vec<builder::Atom>, Error> {
+  let array = values.dyn_ref::<js_sys::Array>()
+    .ok_or_else(|| Error::InvalidAtom("expected an array of atoms".to_string()))?;
+
+  array.iter().map(|v| js_to_atom(&v)).collect()
+}
+
+/// converts a `{ name, ids }` object to a predicate, using `js_to_atom` for the ids
+pub(crate) fn js_to_predicate(value: &JsValue) -> Result<builder::Predicate, Error> {
+  let name = js_sys::Reflect::get(value, &JsValue::from_str("name"))
+    .ok()
+    .and_then(|n| n.as_string())
+    .ok_or_else(|| Error::InvalidAtom("a predicate must have a name".to_string()))?;
+  let ids = js_sys::Reflect::get(value, &JsValue::from_str("ids"))
+    .map_err(|_| Error::InvalidAtom("a predicate must have ids".to_string()))?;
+
+  Ok(builder::Predicate { name, ids: js_to_atoms(&ids)? })
+}
+
+pub(crate) fn js_to_predicates(values: &JsValue) -> Result<Vec<builder::Predicate>, Error> {
+  let array = values.dyn_ref::<js_sys::Array>()
+    .ok_or_else(|| Error::InvalidAtom("expected an array of predicates".to_string()))?;
+
+  array.iter().map(|v| js_to_predicate(&v)).collect()
+}
+
+pub(crate) fn print_predicate(predicate: &builder::Predicate) -> String {
+  let mut symbols = default_symbol_table();
+  let fact = datalog::Fact { predicate: predicate.clone().convert(&mut symbols) };
+  symbols.print_fact(&fact)
+}
+
+pub(crate) fn print_rule(rule: &builder::Rule) -> String {
+  let mut symbols = default_symbol_table();
+  let rule = rule.convert(&mut symbols);
+  symbols.print_rule(&rule)
+}
+
+/// creates an integer atom from a BigInt or a safe integer number
 #[wasm_bindgen]
-pub fn integer(i: i64) -> JsValue {
-  JsValue::from_serde(&Atom { integer: Some(i), ..Default::default() }).unwrap()
+pub fn integer(i: JsValue) -> Result<JsValue, JsValue> {
+  let i = js_to_integer(&i)
+    .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+  Ok(atom_to_js(&builder::int(i)))
 }
 
 #[wasm_bindgen]
 pub fn string(s: &str) -> JsValue {
-  JsValue::from_serde(&Atom { string: Some(s.to_string()), ..Default::default() }).unwrap()
+  atom_to_js(&builder::string(s))
 }
 
 #[wasm_bindgen]
 pub fn symbol(s: &str) -> JsValue {
-  JsValue::from_serde(&Atom { symbol: Some(s.to_string()), ..Default::default() }).unwrap()
+  atom_to_js(&builder::symbol(s))
 }
 
+/// creates a date atom from a `Date` object, an RFC 3339 string or a
+/// number of milliseconds since the Unix epoch
 #[wasm_bindgen]
-pub fn date(i: u64) -> JsValue {
-  JsValue::from_serde(&Atom { date: Some(i), ..Default::default() }).unwrap()
+pub fn date(d: JsValue) -> Result<JsValue, JsValue> {
+  let d = js_to_date(&d)
+    .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+  Ok(atom_to_js(&builder::Atom::Date(d)))
 }
 
 #[wasm_bindgen]
 pub fn variable(s: &str) -> JsValue {
-  JsValue::from_serde(&Atom { variable: Some(s.to_string()), ..Default::default() }).unwrap()
+  atom_to_js(&builder::variable(s))
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -87,10 +457,20 @@ pub struct Predicate {
 }
 
 impl Predicate {
-  pub fn into_predicate(mut self) -> builder::Predicate {
-    builder::Predicate {
+  /// converts to a builder predicate, failing if one of the atoms is invalid
+  pub fn try_into_predicate(mut self) -> Result<builder::Predicate, Error> {
+    Ok(builder::Predicate {
       name: self.name,
-      ids: self.ids.drain(..).map(|a| a.into_atom()).collect(),
+      ids: self.ids.drain(..).map(|a| a.try_into_atom()).collect::<Result<_, _>>()?,
+    })
+  }
+}
+
+impl From<builder::Predicate> for Predicate {
+  fn from(mut p: builder::Predicate) -> Self {
+    Predicate {
+      name: p.name,
+      ids: p.ids.drain(..).map(|a| a.into()).collect(),
     }
   }
 }
@@ -114,18 +494,152 @@ impl Fact {
 #[wasm_bindgen]
 impl Fact {
     #[wasm_bindgen(js_name = fromString)]
-    pub fn from_string(s: &str) -> Fact {
-        let f: builder::Fact = s.parse().expect("parsing error");
-        Fact(f.0)
+    pub fn from_string(s: &str) -> Result<Fact, JsValue> {
+        let f: builder::Fact = s.parse()
+            .map_err(|_| JsValue::from_serde(&Error::ParseError).unwrap())?;
+        Ok(Fact(f.0))
+    }
+
+    /// parses a fact containing `{name}` placeholders, replaced by the
+    /// corresponding value from `params`. The values are converted to atoms
+    /// and never inserted in the datalog text
+    #[wasm_bindgen(js_name = fromTemplate)]
+    pub fn from_template(template: &str, params: JsValue) -> Result<Fact, JsValue> {
+        let params = template_parameters(&params)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        let text = template_to_datalog(template)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        let mut f: builder::Fact = text.parse()
+            .map_err(|_| JsValue::from_serde(&Error::ParseError).unwrap())?;
+        fill_predicate(&mut f.0, &params)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        Ok(Fact(f.0))
+    }
+
+    /// prints the fact in datalog syntax, as accepted by `Fact.fromString`
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_string(&self) -> String {
+        print_predicate(&self.0)
+    }
+
+    /// returns the predicate in the `Predicate` JSON format, as in the
+    /// builders' `toJSON`. It is accepted by `fact()`
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> JsValue {
+        JsValue::from_serde(&Predicate::from(self.0.clone())).unwrap()
     }
 }
 
+const PARAMETER_PREFIX: &str = "__param_";
+
+/// replaces the `{name}` placeholders found outside of string literals
+/// with variables, so that the template can go through the datalog parser
+fn template_to_datalog(template: &str) -> Result<String, Error> {
+  let mut out = String::with_capacity(template.len());
+  let mut chars = template.chars();
+  let mut in_string = false;
+
+  while let Some(c) = chars.next() {
+    if in_string {
+      out.push(c);
+      match c {
+        '\\' => if let Some(escaped) = chars.next() { out.push(escaped) },
+        '"' => in_string = false,
+        _ => {},
+      }
+    } else if c == '"' {
+      in_string = true;
+      out.push(c);
+    } else if c == '{' {
+      let mut name = String::new();
+      loop {
+        match chars.next() {
+          Some('}') if !name.is_empty() => break,
+          Some(c) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+          _ => return Err(Error::InvalidParameter(format!("invalid placeholder {{{}", name))),
+        }
+      }
+
+      out.push('$');
+      out.push_str(PARAMETER_PREFIX);
+      out.push_str(&name);
+    } else {
+      out.push(c);
+    }
+  }
+
+  Ok(out)
+}
+
+fn template_parameters(params: &JsValue) -> Result<HashMap<String, builder::Atom>, Error> {
+  let object = params.dyn_ref::<js_sys::Object>()
+    .ok_or_else(|| Error::InvalidParameter("parameters must be an object".to_string()))?;
+
+  let mut res = HashMap::new();
+  for key in js_sys::Object::keys(object).iter() {
+    let name = key.as_string().unwrap_or_default();
+    let value = js_sys::Reflect::get(object, &key)
+      .map_err(|_| Error::InvalidParameter(name.clone()))?;
+    let atom = js_to_atom(&value)
+      .map_err(|e| Error::InvalidParameter(format!("{}: {:?}", name, e)))?;
+    res.insert(name, atom);
+  }
+
+  Ok(res)
+}
+
+fn fill_predicate(predicate: &mut builder::Predicate, params: &HashMap<String, builder::Atom>) -> Result<(), Error> {
+  for id in predicate.ids.iter_mut() {
+    let name = match id {
+      builder::Atom::Variable(v) if v.starts_with(PARAMETER_PREFIX) => v[PARAMETER_PREFIX.len()..].to_string(),
+      _ => continue,
+    };
+
+    *id = params.get(&name)
+      .cloned()
+      .ok_or_else(|| Error::InvalidParameter(format!("missing parameter {}", name)))?;
+  }
+
+  Ok(())
+}
+
+fn fill_rule(rule: &mut builder::Rule, params: &HashMap<String, builder::Atom>) -> Result<(), Error> {
+  fill_predicate(&mut rule.0, params)?;
+  for predicate in rule.1.iter_mut() {
+    fill_predicate(predicate, params)?;
+  }
+
+  Ok(())
+}
+
+/// creates a fact. The ids can be atoms (`{ string: "file1" }`) or plain
+/// JS values, see `js_to_atom`
 #[wasm_bindgen]
-pub fn fact(name: &str, ids: JsValue) -> Fact {
-    let ids: Vec<Atom> = ids.into_serde().expect("incorrect atom vec");
-    Fact(Predicate { name: name.to_string(), ids}.into_predicate())
+pub fn fact(name: &str, ids: JsValue) -> Result<Fact, JsValue> {
+    let ids = js_to_atoms(&ids)
+        .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+    Ok(Fact(builder::Predicate { name: name.to_string(), ids }))
 }
 
+/// constraint on a variable of a rule, in JSON format
+///
+/// supported combinations:
+///
+/// | kind      | operation                       | data                  |
+/// |-----------|---------------------------------|-----------------------|
+/// | `integer` | `<`, `>`, `<=`, `>=`, `=`, `!=` | integer               |
+/// | `integer` | `in`, `not in`                  | array of integers     |
+/// | `string`  | `prefix`, `suffix`, `regex`     | string                |
+/// | `string`  | `=`, `!=`                       | string                |
+/// | `string`  | `in`, `not in`                  | array of strings      |
+/// | `date`    | `<`, `>`, `<=`, `>=`, `=`       | date                  |
+/// | `symbol`  | `=`, `!=`                       | symbol name           |
+/// | `symbol`  | `in`, `not in`                  | array of symbol names |
+///
+/// `!=` is converted to `not in` with a single element, and date `=` to
+/// a `<=` and a `>=` constraint on the same variable
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Constraint {
   pub id: String,
@@ -160,53 +674,208 @@ pub enum ConstraintKind {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ConstraintData {
-  Integer(i64),
-  IntegerSet(HashSet<i64>),
+  /// integers are serialized as strings, and read as strings by the `String`
+  /// variant, then converted by `into_constraints`
+  Integer(
+    #[serde(serialize_with = "json_integer::serialize", deserialize_with = "json_integer::deserialize_number")]
+    i64
+  ),
+  IntegerSet(
+    #[serde(serialize_with = "json_integer::serialize_set", deserialize_with = "json_integer::deserialize_number_set")]
+    HashSet<i64>
+  ),
   String(String),
   StringSet(HashSet<String>),
   SymbolSet(HashSet<String>),
-  Date(u32),
+  /// Unix timestamp in seconds, serialized in RFC 3339
+  Date(
+    #[serde(serialize_with = "json_date::serialize", deserialize_with = "json_date::deserialize")]
+    u64
+  ),
 }
 
 impl Constraint {
-    pub fn into_constraint(self) -> builder::Constraint {
-      console_log!("into_constraint({:?}, {:?}, {:?}", self.kind, self.operation, self.data);
+    /// converts to builder constraints: most operations give one
+    /// constraint, date equality gives two
+    pub fn into_constraints(self) -> Result<Vec<builder::Constraint>, Error> {
+
+      // dates are read as strings or as integers (in milliseconds) when
+      // deserializing the untagged data, and integers serialized as strings
+      // are read as strings
+      let data = match (&self.kind, self.data) {
+        (ConstraintKind::Date, ConstraintData::Integer(i)) => ConstraintData::Date(milliseconds_to_date(i)?),
+        (ConstraintKind::Date, ConstraintData::String(s)) => ConstraintData::Date(parse_date(&s)?),
+        (ConstraintKind::Integer, ConstraintData::String(s)) => ConstraintData::Integer(parse_integer(&s)?),
+        (ConstraintKind::Integer, ConstraintData::StringSet(s)) | (ConstraintKind::Integer, ConstraintData::SymbolSet(s)) => {
+          ConstraintData::IntegerSet(s.iter().map(|i| parse_integer(i)).collect::<Result<_, _>>()?)
+        },
+        (_, data) => data,
+      };
 
-      let kind = match (self.kind, self.operation.as_str(), self.data) {
+      let kind = match (self.kind, self.operation.as_str(), data) {
         (ConstraintKind::Integer, "<", ConstraintData::Integer(i)) => builder::ConstraintKind::Integer(builder::IntConstraint::Lower(i)),
         (ConstraintKind::Integer, ">", ConstraintData::Integer(i)) => builder::ConstraintKind::Integer(builder::IntConstraint::Larger(i)),
         (ConstraintKind::Integer, "<=", ConstraintData::Integer(i)) => builder::ConstraintKind::Integer(builder::IntConstraint::LowerOrEqual(i)),
         (ConstraintKind::Integer, ">=", ConstraintData::Integer(i)) => builder::ConstraintKind::Integer(builder::IntConstraint::LargerOrEqual(i)),
         (ConstraintKind::Integer, "=", ConstraintData::Integer(i)) => builder::ConstraintKind::Integer(builder::IntConstraint::Equal(i)),
+        (ConstraintKind::Integer, "!=", ConstraintData::Integer(i)) => builder::ConstraintKind::Integer(builder::IntConstraint::NotIn(Some(i).into_iter().collect())),
         (ConstraintKind::Integer, "in", ConstraintData::IntegerSet(s)) => builder::ConstraintKind::Integer(builder::IntConstraint::In(s)),
         (ConstraintKind::Integer, "not in", ConstraintData::IntegerSet(s)) => builder::ConstraintKind::Integer(builder::IntConstraint::NotIn(s)),
 
         (ConstraintKind::String, "prefix", ConstraintData::String(s)) => builder::ConstraintKind::String(builder::StrConstraint::Prefix(s)),
         (ConstraintKind::String, "suffix", ConstraintData::String(s)) => builder::ConstraintKind::String(builder::StrConstraint::Suffix(s)),
+        (ConstraintKind::String, "regex", ConstraintData::String(s)) => builder::ConstraintKind::String(builder::StrConstraint::Regex(s)),
         (ConstraintKind::String, "=", ConstraintData::String(s)) => builder::ConstraintKind::String(builder::StrConstraint::Equal(s)),
+        (ConstraintKind::String, "!=", ConstraintData::String(s)) => builder::ConstraintKind::String(builder::StrConstraint::NotIn(Some(s).into_iter().collect())),
         (ConstraintKind::String, "in", ConstraintData::StringSet(s)) => builder::ConstraintKind::String(builder::StrConstraint::In(s)),
         (ConstraintKind::String, "not in", ConstraintData::StringSet(s)) => builder::ConstraintKind::String(builder::StrConstraint::NotIn(s)),
 
-        (ConstraintKind::Date, "<=", ConstraintData::Integer(i)) => {
-          let t = SystemTime::UNIX_EPOCH + Duration::from_secs(i as u64);
-          builder::ConstraintKind::Date(builder::DateConstraint::Before(t))
+        (ConstraintKind::Date, "<", ConstraintData::Date(i)) => {
+          let i = i.checked_sub(1).ok_or_else(|| Error::InvalidConstraint("no date is before the epoch".to_string()))?;
+          builder::ConstraintKind::Date(builder::DateConstraint::Before(constraint_date(i)?))
         }
-        (ConstraintKind::Date, ">=", ConstraintData::Integer(i)) => {
-          let t = SystemTime::UNIX_EPOCH + Duration::from_secs(i as u64);
-          builder::ConstraintKind::Date(builder::DateConstraint::After(t))
+        (ConstraintKind::Date, "<=", ConstraintData::Date(i)) => {
+          builder::ConstraintKind::Date(builder::DateConstraint::Before(constraint_date(i)?))
+        }
+        (ConstraintKind::Date, ">", ConstraintData::Date(i)) => {
+          let i = i.checked_add(1).ok_or_else(|| Error::InvalidConstraint(format!("no date is after {}", i)))?;
+          builder::ConstraintKind::Date(builder::DateConstraint::After(constraint_date(i)?))
+        }
+        (ConstraintKind::Date, ">=", ConstraintData::Date(i)) => {
+          builder::ConstraintKind::Date(builder::DateConstraint::After(constraint_date(i)?))
+        }
+        (ConstraintKind::Date, "=", ConstraintData::Date(i)) => {
+          let t = constraint_date(i)?;
+          return Ok(vec![
+            builder::Constraint { id: self.id.clone(), kind: builder::ConstraintKind::Date(builder::DateConstraint::After(t)) },
+            builder::Constraint { id: self.id, kind: builder::ConstraintKind::Date(builder::DateConstraint::Before(t)) },
+          ]);
         }
 
-        (ConstraintKind::Symbol, "in", ConstraintData::StringSet(s)) => builder::ConstraintKind::Symbol(builder::SymbolConstraint::In(s)),
-        (ConstraintKind::Symbol, "not in", ConstraintData::StringSet(s)) => builder::ConstraintKind::Symbol(builder::SymbolConstraint::NotIn(s)),
-        (k, _, d) => panic!("invalid constraint: {:?}", Constraint { id: self.id, kind: k, operation: self.operation, data: d }),
+        (ConstraintKind::Symbol, "=", ConstraintData::String(s)) => builder::ConstraintKind::Symbol(builder::SymbolConstraint::In(Some(s).into_iter().collect())),
+        (ConstraintKind::Symbol, "!=", ConstraintData::String(s)) => builder::ConstraintKind::Symbol(builder::SymbolConstraint::NotIn(Some(s).into_iter().collect())),
+        (ConstraintKind::Symbol, "in", ConstraintData::StringSet(s)) | (ConstraintKind::Symbol, "in", ConstraintData::SymbolSet(s)) => builder::ConstraintKind::Symbol(builder::SymbolConstraint::In(s)),
+        (ConstraintKind::Symbol, "not in", ConstraintData::StringSet(s)) | (ConstraintKind::Symbol, "not in", ConstraintData::SymbolSet(s)) => builder::ConstraintKind::Symbol(builder::SymbolConstraint::NotIn(s)),
+        (k, _, d) => return Err(Error::InvalidConstraint(format!("{:?}", Constraint { id: self.id, kind: k, operation: self.operation, data: d }))),
       };
 
-      builder::Constraint {
+      Ok(vec![builder::Constraint {
         id: self.id, kind
-      }
+      }])
     }
 }
 
+/// date of a constraint, failing instead of overflowing `SystemTime`
+pub(crate) fn constraint_date(seconds: u64) -> Result<SystemTime, Error> {
+  SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(seconds))
+    .ok_or_else(|| Error::InvalidConstraint(format!("date out of range: {}", seconds)))
+}
+
+impl From<builder::Constraint> for Constraint {
+  fn from(c: builder::Constraint) -> Self {
+    let (kind, operation, data) = match c.kind {
+      builder::ConstraintKind::Integer(i) => match i {
+        builder::IntConstraint::Lower(i) => (ConstraintKind::Integer, "<", ConstraintData::Integer(i)),
+        builder::IntConstraint::Larger(i) => (ConstraintKind::Integer, ">", ConstraintData::Integer(i)),
+        builder::IntConstraint::LowerOrEqual(i) => (ConstraintKind::Integer, "<=", ConstraintData::Integer(i)),
+        builder::IntConstraint::LargerOrEqual(i) => (ConstraintKind::Integer, ">=", ConstraintData::Integer(i)),
+        builder::IntConstraint::Equal(i) => (ConstraintKind::Integer, "=", ConstraintData::Integer(i)),
+        builder::IntConstraint::In(s) => (ConstraintKind::Integer, "in", ConstraintData::IntegerSet(s)),
+        builder::IntConstraint::NotIn(s) => (ConstraintKind::Integer, "not in", ConstraintData::IntegerSet(s)),
+      },
+      builder::ConstraintKind::String(s) => match s {
+        builder::StrConstraint::Prefix(s) => (ConstraintKind::String, "prefix", ConstraintData::String(s)),
+        builder::StrConstraint::Suffix(s) => (ConstraintKind::String, "suffix", ConstraintData::String(s)),
+        builder::StrConstraint::Equal(s) => (ConstraintKind::String, "=", ConstraintData::String(s)),
+        builder::StrConstraint::Regex(s) => (ConstraintKind::String, "regex", ConstraintData::String(s)),
+        builder::StrConstraint::In(s) => (ConstraintKind::String, "in", ConstraintData::StringSet(s)),
+        builder::StrConstraint::NotIn(s) => (ConstraintKind::String, "not in", ConstraintData::StringSet(s)),
+      },
+      builder::ConstraintKind::Date(d) => match d {
+        builder::DateConstraint::Before(t) => (ConstraintKind::Date, "<=", ConstraintData::Date(unix_seconds(t))),
+        builder::DateConstraint::After(t) => (ConstraintKind::Date, ">=", ConstraintData::Date(unix_seconds(t))),
+      },
+      builder::ConstraintKind::Symbol(s) => match s {
+        builder::SymbolConstraint::In(s) => (ConstraintKind::Symbol, "in", ConstraintData::StringSet(s)),
+        builder::SymbolConstraint::NotIn(s) => (ConstraintKind::Symbol, "not in", ConstraintData::StringSet(s)),
+      },
+    };
+
+    Constraint { id: c.id, kind, operation: operation.to_string(), data }
+  }
+}
+
+/// reads a constraint in the JSON format, or a `Constraint` object. Integer
+/// data is read with `js_to_integer` so that BigInt values are supported,
+/// and date data with `js_to_date`
+pub(crate) fn js_to_constraint(value: &JsValue) -> Result<Constraint, Error> {
+  // `Constraint` objects are read through their JSON representation
+  let to_json = js_sys::Reflect::get(value, &JsValue::from_str("toJSON"))
+    .ok()
+    .and_then(|f| f.dyn_into::<js_sys::Function>().ok());
+  if let Some(to_json) = to_json {
+    let json = to_json.call0(value)
+      .map_err(|_| Error::InvalidConstraint("could not convert the constraint".to_string()))?;
+    return js_to_constraint(&json);
+  }
+
+  let text = |name: &str| {
+    js_sys::Reflect::get(value, &JsValue::from_str(name))
+      .ok()
+      .and_then(|v| v.as_string())
+      .ok_or_else(|| Error::InvalidAtom(format!("a constraint must have a {}", name)))
+  };
+  let data = || {
+    js_sys::Reflect::get(value, &JsValue::from_str("data"))
+      .map_err(|_| Error::InvalidAtom("a constraint must have data".to_string()))
+  };
+
+  match text("kind")?.as_str() {
+    "integer" => {
+      let data = data()?;
+      let data = if let Some(array) = data.dyn_ref::<js_sys::Array>() {
+        ConstraintData::IntegerSet(array.iter().map(|i| js_to_integer(&i)).collect::<Result<_, _>>()?)
+      } else {
+        ConstraintData::Integer(js_to_integer(&data)?)
+      };
+
+      Ok(Constraint { id: text("id")?, kind: ConstraintKind::Integer, operation: text("operation")?, data })
+    },
+    "date" => {
+      let data = ConstraintData::Date(js_to_date(&data()?)?);
+
+      Ok(Constraint { id: text("id")?, kind: ConstraintKind::Date, operation: text("operation")?, data })
+    },
+    _ => value.into_serde().map_err(|e| Error::InvalidAtom(e.to_string())),
+  }
+}
+
+/// converts a constraint to an object in the JSON format, except for the
+/// integers, converted to BigInt, and dates, converted to `Date` objects
+pub(crate) fn constraint_to_js(c: builder::Constraint) -> JsValue {
+  let c: Constraint = c.into();
+  let value = JsValue::from_serde(&c).unwrap();
+  let data = match (&c.kind, &c.data) {
+    (ConstraintKind::Date, ConstraintData::Date(d)) => Some(date_to_js(*d)),
+    (_, ConstraintData::Integer(i)) => Some(integer_to_js(*i)),
+    (_, ConstraintData::IntegerSet(set)) => {
+      let mut v: Vec<i64> = set.iter().cloned().collect();
+      v.sort();
+      Some(v.into_iter().map(integer_to_js).collect::<js_sys::Array>().into())
+    },
+    _ => None,
+  };
+  if let Some(data) = data {
+    js_sys::Reflect::set(&value, &JsValue::from_str("data"), &data).unwrap();
+  }
+
+  value
+}
+
+pub(crate) fn unix_seconds(t: SystemTime) -> u64 {
+  t.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
 
 #[wasm_bindgen()]
 #[derive(Debug, Clone, PartialEq)]
@@ -223,10 +892,70 @@ impl Rule {
 #[wasm_bindgen]
 impl Rule {
     #[wasm_bindgen(js_name = fromString)]
-    pub fn from_string(s: &str) -> Rule {
-        let rule: builder::Rule = s.parse().expect("parsing error");
-        Rule { rule }
+    pub fn from_string(s: &str) -> Result<Rule, JsValue> {
+        let rule: builder::Rule = s.parse()
+            .map_err(|_| JsValue::from_serde(&Error::ParseError).unwrap())?;
+        Ok(Rule { rule })
+    }
+
+    /// parses a rule containing `{name}` placeholders, see `Fact.fromTemplate`.
+    /// Placeholders can only appear in place of atoms, not in constraints
+    #[wasm_bindgen(js_name = fromTemplate)]
+    pub fn from_template(template: &str, params: JsValue) -> Result<Rule, JsValue> {
+        let params = template_parameters(&params)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        let text = template_to_datalog(template)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        let mut rule: builder::Rule = text.parse()
+            .map_err(|_| JsValue::from_serde(&Error::ParseError).unwrap())?;
+        fill_rule(&mut rule, &params)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        Ok(Rule { rule })
+    }
+
+    /// prints the rule in datalog syntax, as accepted by `Rule.fromString`
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_string(&self) -> String {
+        print_rule(&self.rule)
     }
+
+    /// returns the head, body and constraints in the `RuleData` JSON format,
+    /// as in the builders' `toJSON`. They are accepted by `constrained_rule()`
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> JsValue {
+        JsValue::from_serde(&RuleData::from(self.rule.clone())).unwrap()
+    }
+}
+
+/// serializable rule, used to store rules without printing them as datalog
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleData {
+  pub head: Predicate,
+  pub body: Vec<Predicate>,
+  pub constraints: Vec<Constraint>,
+}
+
+impl RuleData {
+  pub fn try_into_rule(self) -> Result<builder::Rule, Error> {
+    Ok(builder::Rule(
+      self.head.try_into_predicate()?,
+      self.body.into_iter().map(|p| p.try_into_predicate()).collect::<Result<_, _>>()?,
+      self.constraints.into_iter().map(|c| c.into_constraints()).collect::<Result<Vec<_>, _>>()?.concat(),
+    ))
+  }
+}
+
+impl From<builder::Rule> for RuleData {
+  fn from(r: builder::Rule) -> Self {
+    let builder::Rule(head, body, constraints) = r;
+    RuleData {
+      head: head.into(),
+      body: body.into_iter().map(|p| p.into()).collect(),
+      constraints: constraints.into_iter().map(|c| c.into()).collect(),
+    }
+  }
 }
 
 #[wasm_bindgen]
@@ -234,14 +963,13 @@ pub fn rule(
     head_name: &str,
     head_ids: JsValue,
     predicates: JsValue,
-) -> Rule {
-    let mut head_ids: Vec<Atom> = head_ids.into_serde().expect("incorrect atom vec");
-    let mut predicates: Vec<Predicate> = predicates.into_serde().unwrap();
-
-    let head_ids = head_ids.drain(..).map(|a| a.into_atom()).collect::<Vec<_>>();
-    let predicates = predicates.drain(..).map(|p| p.into_predicate()).collect::<Vec<_>>();
+) -> Result<Rule, JsValue> {
+    let head_ids = js_to_atoms(&head_ids)
+        .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+    let predicates = js_to_predicates(&predicates)
+        .map_err(|e| JsValue::from_serde(&e).unwrap())?;
 
-    Rule { rule: builder::rule(&head_name, &head_ids, &predicates) }
+    Ok(Rule { rule: builder::rule(&head_name, &head_ids, &predicates) })
 }
 
 #[wasm_bindgen]
@@ -250,16 +978,303 @@ pub fn constrained_rule(
     head_ids: JsValue,
     predicates: JsValue,
     constraints: JsValue,
-) -> Rule {
-    let mut head_ids: Vec<Atom> = head_ids.into_serde().expect("incorrect atom vec");
-    let mut predicates: Vec<Predicate> = predicates.into_serde().unwrap();
-    let mut constraints: Vec<Constraint> = constraints.into_serde().unwrap();
+) -> Result<Rule, JsValue> {
+    let head_ids = js_to_atoms(&head_ids)
+        .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+    let predicates = js_to_predicates(&predicates)
+        .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+    let constraints = constraints.dyn_ref::<js_sys::Array>()
+        .ok_or_else(|| Error::InvalidAtom("expected an array of constraints".to_string()))
+        .and_then(|a| a.iter().map(|c| js_to_constraint(&c)).collect::<Result<Vec<_>, _>>())
+        .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+    let constraints = constraints.into_iter().map(|p| p.into_constraints()).collect::<Result<Vec<_>, _>>()
+        .map_err(|e| JsValue::from_serde(&e).unwrap())?
+        .concat();
+
+    Ok(Rule { rule: builder::constrained_rule(&head_name, &head_ids, &predicates, &constraints) })
+}
+
+/// caveat: a list of queries, at least one of them must return a result
+/// for the caveat to succeed
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caveat {
+    pub(crate) queries: Vec<builder::Rule>,
+}
+
+#[wasm_bindgen]
+impl Caveat {
+    /// parses one or more rules separated by `or`:
+    /// `*admin($0) <- admin(#authority, $0) or *owner($0) <- owner(#authority, $0)`
+    #[wasm_bindgen(js_name = fromString)]
+    pub fn from_string(s: &str) -> Result<Caveat, JsValue> {
+        let queries = split_queries(s).drain(..)
+            .map(|q| q.trim().parse::<builder::Rule>().map_err(|_| Error::ParseError))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        Ok(Caveat { queries })
+    }
+
+    /// parses a caveat containing `{name}` placeholders, see `Fact.fromTemplate`
+    #[wasm_bindgen(js_name = fromTemplate)]
+    pub fn from_template(template: &str, params: JsValue) -> Result<Caveat, JsValue> {
+        let params = template_parameters(&params)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        let text = template_to_datalog(template)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        let queries = split_queries(&text).drain(..)
+            .map(|q| {
+                let mut rule = q.trim().parse::<builder::Rule>().map_err(|_| Error::ParseError)?;
+                fill_rule(&mut rule, &params)?;
+                Ok(rule)
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        Ok(Caveat { queries })
+    }
+
+    #[wasm_bindgen(js_name = fromRule)]
+    pub fn from_rule(rule: Rule) -> Caveat {
+        Caveat { queries: vec![rule.into_rule()] }
+    }
+
+    /// adds an alternative query to the caveat
+    #[wasm_bindgen(js_name = addQuery)]
+    pub fn add_query(&mut self, rule: Rule) {
+        self.queries.push(rule.into_rule());
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_string(&self) -> String {
+        self.queries.iter()
+            .map(print_rule)
+            .collect::<Vec<_>>()
+            .join(" or ")
+    }
+}
+
+impl Caveat {
+    /// tokens store a caveat as a single rule. A caveat with multiple queries
+    /// is converted to:
+    /// - for each query `*head(ids) <- body`, a rule `name_head(#tag, ids) <- body`
+    /// - for each of those, a rule `name(#tag) <- name_head(#tag, $0, ...)`
+    /// - a caveat `*name(#tag) <- name(#tag)`
+    ///
+    /// so that `raise` can restore the queries with their heads. The tag must
+    /// be `authority` or `ambient`, so that other blocks cannot provide the facts
+    pub(crate) fn lower(&self, name: &str, tag: &str) -> (Vec<builder::Rule>, builder::Rule) {
+        if self.queries.len() == 1 {
+            return (vec![], self.queries[0].clone());
+        }
+
+        let head = builder::Predicate { name: name.to_string(), ids: vec![builder::s(tag)] };
+        let mut rules = vec![];
+        let mut connectors = vec![];
+        for builder::Rule(query_head, body, constraints) in self.queries.iter() {
+            let query_head = builder::Predicate {
+                name: format!("{}_{}", name, query_head.name),
+                ids: std::iter::once(builder::s(tag)).chain(query_head.ids.iter().cloned()).collect(),
+            };
+
+            let variables = (0..query_head.ids.len() - 1).map(|i| builder::variable(&i.to_string()));
+            let connector = builder::Rule(
+                head.clone(),
+                vec![builder::Predicate {
+                    name: query_head.name.clone(),
+                    ids: std::iter::once(builder::s(tag)).chain(variables).collect(),
+                }],
+                vec![],
+            );
+            if !connectors.contains(&connector) {
+                connectors.push(connector);
+            }
+
+            rules.push(builder::Rule(query_head, body.clone(), constraints.clone()));
+        }
+        rules.extend(connectors);
+
+        (rules, builder::Rule(head.clone(), vec![head], vec![]))
+    }
+
+    /// restores the caveats lowered by `lower`, named `{prefix}_{caveat index}`,
+    /// removing the rules generated for them
+    pub(crate) fn raise(mut rules: Vec<builder::Rule>, caveats: Vec<builder::Rule>, prefix: &str, tag: &str)
+      -> (Vec<builder::Rule>, Vec<Caveat>) {
+        let caveats = caveats.into_iter().enumerate().map(|(i, caveat)| {
+            let name = format!("{}_{}", prefix, i);
+            let head = builder::Predicate { name: name.clone(), ids: vec![builder::s(tag)] };
+            if caveat != builder::Rule(head.clone(), vec![head], vec![]) {
+                return Caveat { queries: vec![caveat] };
+            }
+
+            let query_prefix = format!("{}_", name);
+            let is_query = |r: &builder::Rule| r.0.name.starts_with(&query_prefix) && has_tag(&r.0, tag);
+            if !rules.iter().any(is_query) {
+                return Caveat { queries: vec![caveat] };
+            }
+
+            let queries = rules.iter()
+                .filter(|r| is_query(r))
+                .map(|builder::Rule(head, body, constraints)| builder::Rule(
+                    builder::Predicate { name: head.name[query_prefix.len()..].to_string(), ids: head.ids[1..].to_vec() },
+                    body.clone(),
+                    constraints.clone(),
+                ))
+                .collect();
+            rules.retain(|r| !is_query(r) && r.0.name != name);
+
+            Caveat { queries }
+        }).collect();
+
+        (rules, caveats)
+    }
+}
+
+/// splits the `or` separated queries of a caveat, ignoring string literals
+fn split_queries(s: &str) -> Vec<&str> {
+  let mut queries = vec![];
+  let mut start = 0;
+  let mut in_string = false;
+  let mut escaped = false;
+
+  for (i, c) in s.char_indices() {
+    if in_string {
+      match c {
+        _ if escaped => escaped = false,
+        '\\' => escaped = true,
+        '"' => in_string = false,
+        _ => {},
+      }
+    } else if c == '"' {
+      in_string = true;
+    } else if i >= start && s[i..].starts_with(" or ") {
+      queries.push(&s[start..i]);
+      start = i + " or ".len();
+    }
+  }
+  queries.push(&s[start..]);
+
+  queries
+}
+
+/// checks if the first id of the predicate is the `#tag` symbol
+pub(crate) fn has_tag(predicate: &builder::Predicate, tag: &str) -> bool {
+  match predicate.ids.first() {
+    Some(builder::Atom::Symbol(s)) => s == tag,
+    _ => false,
+  }
+}
+
+/// adds the `#tag` symbol as first id of the predicate if it is not already there
+pub(crate) fn tag_predicate(mut predicate: builder::Predicate, tag: &str) -> builder::Predicate {
+  if !has_tag(&predicate, tag) {
+    predicate.ids.insert(0, builder::s(tag));
+  }
+
+  predicate
+}
+
+/// lists the variables used in the head or in the constraints of a rule
+/// that do not appear in its body
+pub(crate) fn unbound_variables(rule: &builder::Rule) -> Vec<String> {
+  let builder::Rule(head, body, constraints) = rule;
+  let bound: HashSet<&String> = body.iter()
+    .flat_map(|p| p.ids.iter())
+    .filter_map(|id| match id {
+      builder::Atom::Variable(v) => Some(v),
+      _ => None,
+    })
+    .collect();
+
+  let mut unbound = vec![];
+  let used = head.ids.iter()
+    .filter_map(|id| match id {
+      builder::Atom::Variable(v) => Some(v),
+      _ => None,
+    })
+    .chain(constraints.iter().map(|c| &c.id));
+  for v in used {
+    if !bound.contains(v) && !unbound.contains(v) {
+      unbound.push(v.clone());
+    }
+  }
 
-    let head_ids = head_ids.drain(..).map(|a| a.into_atom()).collect::<Vec<_>>();
-    let predicates = predicates.drain(..).map(|p| p.into_predicate()).collect::<Vec<_>>();
-    let constraints = constraints.drain(..).map(|p| p.into_constraint()).collect::<Vec<_>>();
+  unbound
+}
 
-    Rule { rule: builder::constrained_rule(&head_name, &head_ids, &predicates, &constraints) }
+/// chainable rule builder:
+///
+/// ```js
+/// new RuleBuilder("can_read")
+///   .head([variable("res")])
+///   .body("right", [symbol("authority"), variable("res"), symbol("read")])
+///   .where(StrConstraint.prefix("res", "/a/"))
+///   .build()
+/// ```
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleBuilder {
+    name: String,
+    head: Vec<builder::Atom>,
+    body: Vec<builder::Predicate>,
+    constraints: Vec<builder::Constraint>,
+}
+
+#[wasm_bindgen]
+impl RuleBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(name: &str) -> Self {
+        RuleBuilder {
+            name: name.to_string(),
+            head: vec![],
+            body: vec![],
+            constraints: vec![],
+        }
+    }
+
+    /// sets the ids of the rule head
+    #[wasm_bindgen]
+    pub fn head(mut self, ids: JsValue) -> Result<RuleBuilder, JsValue> {
+        self.head = js_to_atoms(&ids)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        Ok(self)
+    }
+
+    /// adds a predicate to the rule body
+    #[wasm_bindgen]
+    pub fn body(mut self, name: &str, ids: JsValue) -> Result<RuleBuilder, JsValue> {
+        let ids = js_to_atoms(&ids)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        self.body.push(builder::Predicate { name: name.to_string(), ids });
+        Ok(self)
+    }
+
+    /// adds a constraint, either a `Constraint` object or in JSON format
+    #[wasm_bindgen(js_name = where)]
+    pub fn constraint(mut self, constraint: JsValue) -> Result<RuleBuilder, JsValue> {
+        let constraints = js_to_constraint(&constraint)
+            .and_then(|c| c.into_constraints())
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        self.constraints.extend(constraints);
+        Ok(self)
+    }
+
+    /// creates the rule, failing if a variable of the head or of a
+    /// constraint is not bound in the body
+    #[wasm_bindgen]
+    pub fn build(self) -> Result<Rule, JsValue> {
+        let rule = builder::constrained_rule(&self.name, &self.head, &self.body, &self.constraints);
+
+        if let Some(v) = unbound_variables(&rule).into_iter().next() {
+            return Err(JsValue::from_serde(&Error::UnboundVariable(v)).unwrap());
+        }
+
+        Ok(Rule { rule })
+    }
 }
 
 #[wasm_bindgen()]
@@ -268,23 +1283,118 @@ pub struct BiscuitBuilder {
     pub(crate) symbols: SymbolTable,
     pub(crate) facts: Vec<Fact>,
     pub(crate) rules: Vec<Rule>,
-    pub(crate) caveats: Vec<Rule>,
+    pub(crate) caveats: Vec<Caveat>,
+    pub(crate) auto_tag: bool,
+    pub(crate) strict: bool,
+}
+
+/// version of the format written by the builders' `toJSON`
+pub(crate) const DRAFT_FORMAT_VERSION: u32 = 1;
+
+/// facts, rules and caveats of a builder, in a serializable format
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct BlockData {
+    pub facts: Vec<Predicate>,
+    pub rules: Vec<RuleData>,
+    /// queries of each caveat
+    pub caveats: Vec<Vec<RuleData>>,
+}
+
+impl BlockData {
+    pub(crate) fn new(facts: &[Fact], rules: &[Rule], caveats: &[Caveat]) -> Self {
+        BlockData {
+            facts: facts.iter().map(|f| f.0.clone().into()).collect(),
+            rules: rules.iter().map(|r| r.rule.clone().into()).collect(),
+            caveats: caveats.iter()
+                .map(|c| c.queries.iter().map(|q| q.clone().into()).collect())
+                .collect(),
+        }
+    }
+
+    pub(crate) fn into_parts(self) -> Result<(Vec<Fact>, Vec<Rule>, Vec<Caveat>), Error> {
+        let facts = self.facts.into_iter()
+            .map(|p| p.try_into_predicate().map(Fact))
+            .collect::<Result<_, _>>()?;
+        let rules = self.rules.into_iter()
+            .map(|r| r.try_into_rule().map(|rule| Rule { rule }))
+            .collect::<Result<_, _>>()?;
+        let caveats = self.caveats.into_iter()
+            .map(|queries| queries.into_iter().map(|q| q.try_into_rule()).collect::<Result<_, _>>()
+                .map(|queries| Caveat { queries }))
+            .collect::<Result<_, _>>()?;
+
+        Ok((facts, rules, caveats))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct BiscuitBuilderData {
+    version: u32,
+    symbols: Vec<String>,
+    #[serde(flatten)]
+    block: BlockData,
+    auto_tag: bool,
+    strict: bool,
+}
+
+/// facts in the format of the `facts` of the builders' `toJSON`, for `listFacts`
+pub(crate) fn facts_to_js(facts: &[Fact]) -> js_sys::Array {
+    facts.iter()
+        .map(|f| JsValue::from_serde(&Predicate::from(f.0.clone())).unwrap())
+        .collect()
+}
+
+/// checks the version of a draft loaded with `fromJSON`
+pub(crate) fn check_draft_version(version: u32) -> Result<(), Error> {
+    if version == DRAFT_FORMAT_VERSION {
+        Ok(())
+    } else {
+        Err(Error::Format(crate::error::Format::DeserializationError(
+            format!("unsupported draft format version {}", version))))
+    }
+}
+
+/// removes the element at `index`, for the builders' `remove*` methods
+pub(crate) fn remove_at<T>(elements: &mut Vec<T>, index: usize, kind: &str) -> Result<T, JsValue> {
+    if index < elements.len() {
+        Ok(elements.remove(index))
+    } else {
+        Err(JsValue::from_serde(&Error::InvalidParameter(format!("no {} at index {}", kind, index))).unwrap())
+    }
 }
 
 #[wasm_bindgen()]
 impl BiscuitBuilder {
+    /// creates a builder with a custom base symbol table, validated like
+    /// in the `SymbolTable` constructor
     #[wasm_bindgen(constructor)]
-    pub fn new(base_symbols: JsValue) -> Self {
-        let symbol_strings: Vec<String> = base_symbols.into_serde().expect("Can't format symbols table");
-        let symbols = SymbolTable { symbols: symbol_strings };
+    pub fn new(base_symbols: JsValue) -> Result<BiscuitBuilder, JsValue> {
+        let symbols = crate::SymbolTable::new(base_symbols)?;
+        Ok(BiscuitBuilder::with_symbols(&symbols))
+    }
+
+    #[wasm_bindgen(js_name = withSymbols)]
+    pub fn with_symbols(symbols: &crate::SymbolTable) -> Self {
         Self {
-            symbols,
+            symbols: symbols.0.clone(),
             facts: vec![],
             rules: vec![],
             caveats: vec![],
+            auto_tag: false,
+            strict: false,
         }
     }
 
+    /// replaces the base symbol table. Symbols of the base table are not
+    /// stored in the token, so a table containing the common predicate
+    /// names makes tokens smaller, but they must then be deserialized with
+    /// `Biscuit.fromWithSymbols` and the same table
+    #[wasm_bindgen(js_name = setSymbols)]
+    pub fn set_symbols(&mut self, symbols: &crate::SymbolTable) {
+        self.symbols = symbols.0.clone();
+    }
+
     #[wasm_bindgen(js_name = newWithDefaultSymbols)]
     pub fn new_with_default_symbols() -> Self {
         let symbols = default_symbol_table();
@@ -293,6 +1403,8 @@ impl BiscuitBuilder {
             facts: vec![],
             rules: vec![],
             caveats: vec![],
+            auto_tag: false,
+            strict: false,
         }
     }
 
@@ -307,48 +1419,170 @@ impl BiscuitBuilder {
     }
 
     #[wasm_bindgen(js_name = addAuthorityCaveat)]
-    pub fn add_authority_caveat(&mut self, rule_bind: Rule) {
-        self.caveats.push(rule_bind);
+    pub fn add_authority_caveat(&mut self, caveat: Caveat) {
+        self.caveats.push(caveat);
+    }
+
+    /// when enabled, authority facts that do not start with `#authority`
+    /// get it added when building the token
+    #[wasm_bindgen(js_name = setAutoTag)]
+    pub fn set_auto_tag(&mut self, enabled: bool) {
+        self.auto_tag = enabled;
+    }
+
+    /// when enabled, `build` calls `validate` first
+    #[wasm_bindgen(js_name = setStrict)]
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    /// checks the authority block content before signing it, returning
+    /// the errors the verifier would report
+    #[wasm_bindgen]
+    pub fn validate(&self) -> Result<(), JsValue> {
+        self.check().map_err(|e| JsValue::from_serde(&e).unwrap())
     }
 
     #[wasm_bindgen(js_name = addRight)]
     pub fn add_right(&mut self, resource: &str, right: &str) {
-        self.add_authority_fact(Fact(Predicate{
+        self.add_authority_fact(Fact(builder::Predicate {
             name: "right".to_string(),
-            ids: vec![
-              Atom { string: Some("authority".to_string()), ..Default::default()},
-              Atom { string: Some(resource.to_string()), ..Default::default()},
-              Atom { symbol: Some(right.to_string()), ..Default::default() }],
-        }.into_predicate()));
+            ids: vec![builder::symbol("authority"), builder::string(resource), builder::symbol(right)],
+        }));
+    }
+
+    /// signs the authority block. The key pair is borrowed, so that its
+    /// public key can still be used to verify the token
+    #[wasm_bindgen]
+    pub fn build(self, root: &crate::crypto::KeyPair) -> Result<Biscuit, JsValue> {
+        self.build_signed(root)
     }
 
     #[wasm_bindgen]
-    pub fn build(self, root: crate::crypto::KeyPair) -> Result<Biscuit, JsValue> {
+    pub fn print(&self, root: &crate::crypto::KeyPair) -> Result<String, JsValue> {
+      Ok(self.clone().build(root)?.print())
+    }
+
+    /// returns the authority facts in the format of the `facts` of `toJSON`
+    #[wasm_bindgen(js_name = listFacts)]
+    pub fn list_facts(&self) -> js_sys::Array {
+        facts_to_js(&self.facts)
+    }
+
+    #[wasm_bindgen(js_name = removeFact)]
+    pub fn remove_fact(&mut self, index: usize) -> Result<Fact, JsValue> {
+        remove_at(&mut self.facts, index, "fact")
+    }
+
+    #[wasm_bindgen(js_name = removeRule)]
+    pub fn remove_rule(&mut self, index: usize) -> Result<Rule, JsValue> {
+        remove_at(&mut self.rules, index, "rule")
+    }
+
+    #[wasm_bindgen(js_name = removeCaveat)]
+    pub fn remove_caveat(&mut self, index: usize) -> Result<Caveat, JsValue> {
+        remove_at(&mut self.caveats, index, "caveat")
+    }
+
+    /// removes the facts, rules and caveats, keeping the symbols and settings
+    #[wasm_bindgen]
+    pub fn clear(&mut self) {
+        self.facts.clear();
+        self.rules.clear();
+        self.caveats.clear();
+    }
+
+    /// returns the draft as an object that can be stored and loaded with
+    /// `fromJSON`. Integers are decimal strings and dates are RFC 3339 strings
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> JsValue {
+        let data = BiscuitBuilderData {
+            version: DRAFT_FORMAT_VERSION,
+            symbols: self.symbols.symbols.clone(),
+            block: BlockData::new(&self.facts, &self.rules, &self.caveats),
+            auto_tag: self.auto_tag,
+            strict: self.strict,
+        };
+
+        JsValue::from_serde(&data).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = fromJSON)]
+    pub fn from_json(value: JsValue) -> Result<BiscuitBuilder, JsValue> {
+        let data: BiscuitBuilderData = value.into_serde()
+            .map_err(|e| Error::Format(crate::error::Format::DeserializationError(e.to_string())))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        check_draft_version(data.version)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        let symbols = crate::SymbolTable::validated(data.symbols)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        let (facts, rules, caveats) = data.block.into_parts()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        Ok(BiscuitBuilder {
+            symbols: symbols.0,
+            facts,
+            rules,
+            caveats,
+            auto_tag: data.auto_tag,
+            strict: data.strict,
+        })
+    }
+}
+
+impl BiscuitBuilder {
+    pub(crate) fn build_signed(self, root: &crate::crypto::KeyPair) -> Result<Biscuit, JsValue> {
+        if self.strict {
+            self.validate()?;
+        }
+
         let mut rng = OsRng;
         let symbols = self.symbols;
-        let mut builder = token::Biscuit::builder_with_symbols(&root.0, symbols);
+        let mut builder = token::Biscuit::builder_with_symbols(&root.0, symbols.clone());
 
         for fact in self.facts {
-          builder.add_authority_fact(fact.into_fact());
+          if self.auto_tag {
+            builder.add_authority_fact(builder::Fact(tag_predicate(fact.0, "authority")));
+          } else {
+            builder.add_authority_fact(fact.into_fact());
+          }
         }
 
         for rule in self.rules {
           builder.add_authority_rule(rule.into_rule());
         }
 
-        for caveat in self.caveats {
-          builder.add_authority_caveat(caveat.into_rule());
+        for (i, caveat) in self.caveats.iter().enumerate() {
+          let (rules, caveat) = caveat.lower(&format!("caveat_query_authority_{}", i), "authority");
+          for rule in rules {
+            builder.add_authority_rule(rule);
+          }
+          builder.add_authority_caveat(caveat);
         }
 
         builder.build(&mut rng)
             .map_err(|e| { let e: crate::error::Error = e.into(); e})
             .map_err(|e| JsValue::from_serde(&e).unwrap())
-            .map(Biscuit)
+            .map(|token| Biscuit { token, symbols, source: None })
     }
 
-    #[wasm_bindgen]
-    pub fn print(&self, root: crate::crypto::KeyPair) -> Result<String, JsValue> {
-      Ok(self.clone().build(root).unwrap().print())
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        for fact in self.facts.iter() {
+            if !self.auto_tag && !has_tag(&fact.0, "authority") {
+                return Err(Error::FailedLogic(crate::error::Logic::InvalidAuthorityFact(fact.to_string())));
+            }
+        }
+
+        // same errors as `BlockBuilder.validate`, the authority block being block 0
+        let queries = self.caveats.iter().flat_map(|c| c.queries.iter());
+        for rule in self.rules.iter().map(|r| &r.rule).chain(queries) {
+            if !unbound_variables(rule).is_empty() {
+                return Err(Error::FailedLogic(crate::error::Logic::InvalidBlockRule(
+                  crate::error::InvalidBlockRule { block_id: 0, rule: print_rule(rule) })));
+            }
+        }
+
+        Ok(())
     }
 }
 
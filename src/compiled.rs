@@ -0,0 +1,159 @@
+use biscuit::token::{self, builder};
+use wasm_bindgen::prelude::*;
+
+use std::time::{Duration, SystemTime};
+
+use crate::Biscuit;
+use crate::builder::{has_tag, js_to_date, js_to_predicates, print_predicate, tag_predicate};
+use crate::error::{Error, Logic, FailedCaveat};
+
+/// verifier facts, rules and caveats checked and lowered once, then given
+/// to the token verifier for each token
+#[derive(Clone)]
+pub(crate) struct Compiled {
+    facts: Vec<builder::Fact>,
+    rules: Vec<builder::Rule>,
+    /// lowered caveat and text of the original caveat
+    caveats: Vec<(builder::Rule, String)>,
+    auto_tag: bool,
+    /// revocation ids rejected by the verifier
+    revoked: Vec<i64>,
+}
+
+impl Compiled {
+    pub(crate) fn new(
+        facts: impl Iterator<Item = builder::Fact>,
+        rules: impl Iterator<Item = builder::Rule>,
+        caveats: impl Iterator<Item = (builder::Rule, String)>,
+        auto_tag: bool,
+        revoked: Vec<i64>,
+    ) -> Result<Self, Error> {
+        let facts = facts.collect::<Vec<_>>();
+        if let Some(fact) = facts.iter().find(|f| !has_tag(&f.0, "ambient")) {
+            return Err(Error::FailedLogic(Logic::InvalidAmbientFact(print_predicate(&fact.0))));
+        }
+
+        Ok(Compiled { facts, rules: rules.collect(), caveats: caveats.collect(), auto_tag, revoked })
+    }
+
+    /// verifies the token with the token verifier, adding `facts` to the
+    /// verifier's facts
+    pub(crate) fn verified<'b>(&self, root_key: &crate::crypto::PublicKey, biscuit: &'b Biscuit, facts: &[builder::Fact])
+      -> Result<token::verifier::Verifier<'b>, Error> {
+        let mut verifier = biscuit.token.verify(root_key.0)
+            .map_err(|e| { let e: Error = e.into(); e})?;
+        biscuit.check_revocation(&self.revoked)?;
+
+        for fact in self.facts.iter() {
+            verifier.add_fact(fact.clone());
+        }
+
+        for fact in facts.iter() {
+            if self.auto_tag {
+                verifier.add_fact(builder::Fact(tag_predicate(fact.0.clone(), "ambient")));
+            } else {
+                verifier.add_fact(fact.clone());
+            }
+        }
+
+        for rule in self.rules.iter() {
+            verifier.add_rule(rule.clone());
+        }
+
+        for (caveat, _) in self.caveats.iter() {
+            verifier.add_caveat(caveat.clone());
+        }
+
+        verifier.verify()
+          .map_err(|e| { let e: Error = e.into(); e})
+          .map_err(|e| biscuit.print_failed_caveats(self.print_failed_caveats(e)))?;
+
+        Ok(verifier)
+    }
+
+    /// like `verified`, but returns the failed caveats instead of an error
+    pub(crate) fn failed_caveats(&self, root_key: &crate::crypto::PublicKey, biscuit: &Biscuit, facts: &[builder::Fact])
+      -> Result<Vec<FailedCaveat>, Error> {
+        match self.verified(root_key, biscuit, facts) {
+            Ok(_) => Ok(vec![]),
+            Err(Error::FailedLogic(Logic::FailedCaveats(failed))) => Ok(failed),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// replaces the rules generated for verifier caveats with the caveats
+    /// themselves in the error
+    fn print_failed_caveats(&self, mut e: Error) -> Error {
+        if let Error::FailedLogic(Logic::FailedCaveats(ref mut failed)) = e {
+            for f in failed.iter_mut() {
+                if let FailedCaveat::Verifier(ref mut v) = f {
+                    if let Some((_, text)) = self.caveats.get(v.caveat_id as usize) {
+                        v.rule = text.clone();
+                    }
+                }
+            }
+        }
+
+        e
+    }
+}
+
+/// verifier with its facts checked and its caveats lowered once, created
+/// with `Verifier.compile`
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct CompiledVerifier(pub(crate) Compiled);
+
+#[wasm_bindgen]
+impl CompiledVerifier {
+    /// verifies the token, adding the facts of the request to the compiled ones:
+    ///
+    /// ```text
+    /// { resource: "file1", operation: "read", time: new Date(), facts: [{ name, ids }] }
+    /// ```
+    ///
+    /// each field is optional, and the request itself can be omitted
+    #[wasm_bindgen]
+    pub fn verify(&self, root_key: &crate::crypto::PublicKey, biscuit: &Biscuit, request: JsValue) -> Result<(), JsValue> {
+        let facts = request_facts(&request)
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+
+        self.0.verified(root_key, biscuit, &facts)
+            .map(|_| ())
+            .map_err(|e| e.to_js())
+    }
+}
+
+fn request_facts(request: &JsValue) -> Result<Vec<builder::Fact>, Error> {
+    let mut facts = Vec::new();
+    if request.is_undefined() || request.is_null() {
+        return Ok(facts);
+    }
+
+    let field = |name: &str| js_sys::Reflect::get(request, &JsValue::from_str(name))
+        .map(|v| if v.is_undefined() || v.is_null() { None } else { Some(v) })
+        .map_err(|_| Error::InvalidParameter("expected a request object".to_string()));
+
+    if let Some(resource) = field("resource")? {
+        let resource = resource.as_string()
+            .ok_or_else(|| Error::InvalidParameter("the resource must be a string".to_string()))?;
+        facts.push(builder::fact("resource", &[builder::s("ambient"), builder::string(&resource)]));
+    }
+
+    if let Some(operation) = field("operation")? {
+        let operation = operation.as_string()
+            .ok_or_else(|| Error::InvalidParameter("the operation must be a string".to_string()))?;
+        facts.push(builder::fact("operation", &[builder::s("ambient"), builder::s(&operation)]));
+    }
+
+    if let Some(time) = field("time")? {
+        let t = SystemTime::UNIX_EPOCH + Duration::from_secs(js_to_date(&time)?);
+        facts.push(builder::fact("time", &[builder::s("ambient"), builder::date(&t)]));
+    }
+
+    if let Some(predicates) = field("facts")? {
+        facts.extend(js_to_predicates(&predicates)?.into_iter().map(builder::Fact));
+    }
+
+    Ok(facts)
+}
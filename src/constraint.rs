@@ -0,0 +1,191 @@
+use biscuit::token::builder;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use std::collections::HashSet;
+use std::time::SystemTime;
+
+use crate::builder::{constraint_date, js_to_date, js_to_integer};
+use crate::error::Error;
+
+/// constraint on a rule variable, created with `IntConstraint`,
+/// `StrConstraint`, `DateConstraint` or `SymbolConstraint`
+///
+/// it can be used wherever a constraint in JSON format is accepted
+#[wasm_bindgen(js_name = Constraint)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleConstraint(pub(crate) builder::Constraint);
+
+#[wasm_bindgen(js_class = Constraint)]
+impl RuleConstraint {
+    /// returns the constraint in the `Constraint` JSON format
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> JsValue {
+        JsValue::from_serde(&crate::builder::Constraint::from(self.0.clone())).unwrap()
+    }
+}
+
+impl RuleConstraint {
+    fn new(variable: &str, kind: builder::ConstraintKind) -> Self {
+        RuleConstraint(builder::Constraint { id: variable.to_string(), kind })
+    }
+}
+
+fn integer(value: &JsValue) -> Result<i64, JsValue> {
+    js_to_integer(value).map_err(|e| JsValue::from_serde(&e).unwrap())
+}
+
+fn integer_set(values: &JsValue) -> Result<HashSet<i64>, JsValue> {
+    values.dyn_ref::<js_sys::Array>()
+        .ok_or_else(|| Error::InvalidConstraint("expected an array of integers".to_string()))
+        .and_then(|a| a.iter().map(|i| js_to_integer(&i)).collect())
+        .map_err(|e| JsValue::from_serde(&e).unwrap())
+}
+
+fn string_set(values: &JsValue) -> Result<HashSet<String>, JsValue> {
+    values.dyn_ref::<js_sys::Array>()
+        .and_then(|a| a.iter().map(|s| s.as_string()).collect())
+        .ok_or_else(|| Error::InvalidConstraint("expected an array of strings".to_string()))
+        .map_err(|e| JsValue::from_serde(&e).unwrap())
+}
+
+fn date(value: &JsValue) -> Result<SystemTime, JsValue> {
+    js_to_date(value)
+        .and_then(constraint_date)
+        .map_err(|e| JsValue::from_serde(&e).unwrap())
+}
+
+fn single<T: std::hash::Hash + Eq>(value: T) -> HashSet<T> {
+    Some(value).into_iter().collect()
+}
+
+#[wasm_bindgen]
+pub struct IntConstraint;
+
+#[wasm_bindgen]
+impl IntConstraint {
+    #[wasm_bindgen(js_name = lessThan)]
+    pub fn less_than(variable: &str, i: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Integer(builder::IntConstraint::Lower(integer(&i)?))))
+    }
+
+    #[wasm_bindgen(js_name = greaterThan)]
+    pub fn greater_than(variable: &str, i: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Integer(builder::IntConstraint::Larger(integer(&i)?))))
+    }
+
+    #[wasm_bindgen(js_name = lessOrEqual)]
+    pub fn less_or_equal(variable: &str, i: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Integer(builder::IntConstraint::LowerOrEqual(integer(&i)?))))
+    }
+
+    #[wasm_bindgen(js_name = greaterOrEqual)]
+    pub fn greater_or_equal(variable: &str, i: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Integer(builder::IntConstraint::LargerOrEqual(integer(&i)?))))
+    }
+
+    #[wasm_bindgen]
+    pub fn equal(variable: &str, i: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Integer(builder::IntConstraint::Equal(integer(&i)?))))
+    }
+
+    #[wasm_bindgen(js_name = notEqual)]
+    pub fn not_equal(variable: &str, i: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Integer(builder::IntConstraint::NotIn(single(integer(&i)?)))))
+    }
+
+    #[wasm_bindgen(js_name = in)]
+    pub fn in_set(variable: &str, set: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Integer(builder::IntConstraint::In(integer_set(&set)?))))
+    }
+
+    #[wasm_bindgen(js_name = notIn)]
+    pub fn not_in(variable: &str, set: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Integer(builder::IntConstraint::NotIn(integer_set(&set)?))))
+    }
+}
+
+#[wasm_bindgen]
+pub struct StrConstraint;
+
+#[wasm_bindgen]
+impl StrConstraint {
+    #[wasm_bindgen]
+    pub fn prefix(variable: &str, s: &str) -> RuleConstraint {
+        RuleConstraint::new(variable, builder::ConstraintKind::String(builder::StrConstraint::Prefix(s.to_string())))
+    }
+
+    #[wasm_bindgen]
+    pub fn suffix(variable: &str, s: &str) -> RuleConstraint {
+        RuleConstraint::new(variable, builder::ConstraintKind::String(builder::StrConstraint::Suffix(s.to_string())))
+    }
+
+    #[wasm_bindgen]
+    pub fn regex(variable: &str, s: &str) -> RuleConstraint {
+        RuleConstraint::new(variable, builder::ConstraintKind::String(builder::StrConstraint::Regex(s.to_string())))
+    }
+
+    #[wasm_bindgen]
+    pub fn equal(variable: &str, s: &str) -> RuleConstraint {
+        RuleConstraint::new(variable, builder::ConstraintKind::String(builder::StrConstraint::Equal(s.to_string())))
+    }
+
+    #[wasm_bindgen(js_name = notEqual)]
+    pub fn not_equal(variable: &str, s: &str) -> RuleConstraint {
+        RuleConstraint::new(variable, builder::ConstraintKind::String(builder::StrConstraint::NotIn(single(s.to_string()))))
+    }
+
+    #[wasm_bindgen(js_name = in)]
+    pub fn in_set(variable: &str, set: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::String(builder::StrConstraint::In(string_set(&set)?))))
+    }
+
+    #[wasm_bindgen(js_name = notIn)]
+    pub fn not_in(variable: &str, set: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::String(builder::StrConstraint::NotIn(string_set(&set)?))))
+    }
+}
+
+#[wasm_bindgen]
+pub struct DateConstraint;
+
+#[wasm_bindgen]
+impl DateConstraint {
+    /// the date must be before or equal to `date`
+    #[wasm_bindgen]
+    pub fn before(variable: &str, date: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Date(builder::DateConstraint::Before(self::date(&date)?))))
+    }
+
+    /// the date must be after or equal to `date`
+    #[wasm_bindgen]
+    pub fn after(variable: &str, date: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Date(builder::DateConstraint::After(self::date(&date)?))))
+    }
+}
+
+#[wasm_bindgen]
+pub struct SymbolConstraint;
+
+#[wasm_bindgen]
+impl SymbolConstraint {
+    #[wasm_bindgen]
+    pub fn equal(variable: &str, s: &str) -> RuleConstraint {
+        RuleConstraint::new(variable, builder::ConstraintKind::Symbol(builder::SymbolConstraint::In(single(s.to_string()))))
+    }
+
+    #[wasm_bindgen(js_name = notEqual)]
+    pub fn not_equal(variable: &str, s: &str) -> RuleConstraint {
+        RuleConstraint::new(variable, builder::ConstraintKind::Symbol(builder::SymbolConstraint::NotIn(single(s.to_string()))))
+    }
+
+    #[wasm_bindgen(js_name = in)]
+    pub fn in_set(variable: &str, set: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Symbol(builder::SymbolConstraint::In(string_set(&set)?))))
+    }
+
+    #[wasm_bindgen(js_name = notIn)]
+    pub fn not_in(variable: &str, set: JsValue) -> Result<RuleConstraint, JsValue> {
+        Ok(RuleConstraint::new(variable, builder::ConstraintKind::Symbol(builder::SymbolConstraint::NotIn(string_set(&set)?))))
+    }
+}
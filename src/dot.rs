@@ -0,0 +1,139 @@
+use biscuit::datalog::{self, SymbolTable, World};
+use wasm_bindgen::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::Biscuit;
+
+/// Graphviz DOT writer
+///
+/// blocks are rendered as clusters, facts, rules and caveats as nodes,
+/// and every rule has an edge to the facts it generates
+pub(crate) struct Dot {
+    out: String,
+    facts: HashMap<datalog::Fact, String>,
+    rules: Vec<(String, datalog::Rule)>,
+}
+
+impl Dot {
+    pub fn new(name: &str) -> Self {
+        Dot {
+            out: format!("digraph {} {{\n", name),
+            facts: HashMap::new(),
+            rules: vec![],
+        }
+    }
+
+    pub fn cluster(
+        &mut self,
+        id: &str,
+        label: &str,
+        symbols: &SymbolTable,
+        facts: &[datalog::Fact],
+        rules: &[datalog::Rule],
+        caveats: &[datalog::Rule],
+    ) {
+        self.out.push_str(&format!("  subgraph cluster_{} {{\n    label=\"{}\";\n", id, escape(label)));
+
+        for (i, fact) in facts.iter().enumerate() {
+            let node = format!("{}_fact_{}", id, i);
+            self.node(&node, &symbols.print_fact(fact), "box");
+            self.facts.entry(fact.clone()).or_insert(node);
+        }
+
+        for (i, rule) in rules.iter().enumerate() {
+            let node = format!("{}_rule_{}", id, i);
+            self.node(&node, &symbols.print_rule(rule), "ellipse");
+            self.rules.push((node, rule.clone()));
+        }
+
+        for (i, caveat) in caveats.iter().enumerate() {
+            let node = format!("{}_caveat_{}", id, i);
+            self.node(&node, &symbols.print_rule(caveat), "diamond");
+        }
+
+        self.out.push_str("  }\n");
+    }
+
+    /// adds the facts generated by running the world, and the edges from
+    /// rules to the facts they produce
+    pub fn derived(&mut self, world: &World, symbols: &SymbolTable) {
+        let mut derived: Vec<_> = world.facts.iter()
+            .filter(|f| !self.facts.contains_key(f))
+            .cloned()
+            .collect();
+        derived.sort_by_key(|f| symbols.print_fact(f));
+
+        if !derived.is_empty() {
+            self.out.push_str("  subgraph cluster_derived {\n    label=\"derived\";\n");
+            for (i, fact) in derived.drain(..).enumerate() {
+                let node = format!("derived_fact_{}", i);
+                self.node(&node, &symbols.print_fact(&fact), "box");
+                self.facts.insert(fact, node);
+            }
+            self.out.push_str("  }\n");
+        }
+
+        let mut edges = String::new();
+        for (node, rule) in self.rules.iter() {
+            let mut targets: Vec<&String> = world.query_rule(rule.clone()).iter()
+                .filter_map(|f| self.facts.get(f))
+                .collect();
+            targets.sort();
+            targets.dedup();
+
+            for target in targets {
+                edges.push_str(&format!("  {} -> {};\n", node, target));
+            }
+        }
+        self.out.push_str(&edges);
+    }
+
+    pub fn finish(mut self) -> String {
+        self.out.push_str("}\n");
+        self.out
+    }
+
+    fn node(&mut self, id: &str, label: &str, shape: &str) {
+        self.out.push_str(&format!("    {} [label=\"{}\", shape={}];\n", id, escape(label), shape));
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Biscuit {
+    pub(crate) fn write_blocks(&self, dot: &mut Dot, symbols: &SymbolTable) -> Result<(), crate::error::Error> {
+        for block in self.blocks()? {
+            let (id, label) = if block.index == 0 {
+                ("block_0".to_string(), "authority".to_string())
+            } else {
+                (format!("block_{}", block.index), format!("block {}", block.index))
+            };
+
+            dot.cluster(&id, &label, symbols, &block.facts, &block.rules, &block.caveats);
+        }
+
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+impl Biscuit {
+    #[wasm_bindgen(js_name = toDot)]
+    pub fn to_dot(&self) -> Result<String, JsValue> {
+        let symbols = self.symbol_table()
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        let mut world = self.world()
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        world.run();
+
+        let mut dot = Dot::new("biscuit");
+        self.write_blocks(&mut dot, &symbols)
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        dot.derived(&world, &symbols);
+
+        Ok(dot.finish())
+    }
+}
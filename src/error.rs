@@ -1,5 +1,6 @@
 use biscuit::error;
 use serde::{Serialize, Deserialize};
+use wasm_bindgen::JsValue;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "kind", content = "data")]
@@ -21,6 +22,16 @@ pub enum Error {
     FailedLogic(Logic),
     /// Datalog parsing error
     ParseError,
+    /// a template parameter is missing or has an unsupported type
+    InvalidParameter(String),
+    /// a JS value could not be converted to an atom
+    InvalidAtom(String),
+    /// the combination of kind, operation and data is not supported
+    InvalidConstraint(String),
+    /// a variable of the rule head or of a constraint does not appear in the rule body
+    UnboundVariable(String),
+    /// a caveat with multiple queries was added to a block other than the authority block
+    InvalidCaveat(String),
 }
 
 impl From<error::Token> for Error {
@@ -39,6 +50,21 @@ impl From<error::Token> for Error {
     }
 }
 
+impl Error {
+    /// converts the error to a JS object in the serde format, except for
+    /// the ids of `Revoked` errors, converted to BigInt
+    pub(crate) fn to_js(&self) -> JsValue {
+      let value = JsValue::from_serde(self).expect("error serde");
+      if let Error::FailedLogic(Logic::Revoked(ids)) = self {
+        let ids: js_sys::Array = ids.iter().cloned().map(crate::builder::integer_to_js).collect();
+        let logic = js_sys::Reflect::get(&value, &JsValue::from_str("data")).unwrap();
+        js_sys::Reflect::set(&logic, &JsValue::from_str("data"), &ids).unwrap();
+      }
+
+      value
+    }
+}
+
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct InvalidBlockIndex {
@@ -70,6 +96,16 @@ pub enum Format {
     BlockDeserializationError(String),
     /// could not serialize the block
     BlockSerializationError(String),
+    /// the token exceeds one of the parse limits
+    LimitExceeded(LimitExceeded),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LimitExceeded {
+    /// name of the parse option
+    pub limit: String,
+    pub max: usize,
+    pub found: usize,
 }
 
 impl From<error::Format> for Format {
@@ -119,6 +155,12 @@ pub enum Logic {
     InvalidBlockRule(InvalidBlockRule),
     /// list of caveats that failed validation
     FailedCaveats(Vec<FailedCaveat>),
+    /// the token contains revocation ids rejected by `Verifier.revocationCheck`,
+    /// serialized as decimal strings
+    Revoked(
+      #[serde(serialize_with = "crate::builder::json_integer::serialize_vec", deserialize_with = "crate::builder::json_integer::deserialize_vec")]
+      Vec<i64>
+    ),
 }
 
 impl From<error::Logic> for Logic {
@@ -135,14 +177,14 @@ impl From<error::Logic> for Logic {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct InvalidBlockFact {
-  block_id: u32,
-  fact: String,
+  pub block_id: u32,
+  pub fact: String,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct InvalidBlockRule {
-  block_id: u32,
-  rule: String,
+  pub block_id: u32,
+  pub rule: String,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -167,14 +209,14 @@ impl From<error::FailedCaveat> for FailedCaveat {
 pub struct FailedBlockCaveat {
     pub block_id: u32,
     pub caveat_id: u32,
-    /// pretty print of the rule that failed
+    /// pretty print of the caveat that failed, with its queries separated by `or`
     pub rule: String,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct FailedVerifierCaveat {
     pub caveat_id: u32,
-    /// pretty print of the rule that failed
+    /// pretty print of the caveat that failed, with its queries separated by `or`
     pub rule: String,
 }
 
@@ -2,13 +2,24 @@ extern crate biscuit_auth as biscuit;
 
 use biscuit::datalog;
 use biscuit::token;
+use biscuit::format::{schema, convert};
+use prost::Message;
 use wasm_bindgen::prelude::*;
 use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
 
 pub mod builder;
+pub mod compiled;
+pub mod constraint;
 pub mod crypto;
 pub mod verifier;
 pub mod error;
+pub mod dot;
+pub mod limits;
+pub mod lint;
+pub mod requirements;
+pub mod size;
+pub mod spec;
 
 extern crate wee_alloc;
 #[global_allocator]
@@ -17,13 +28,76 @@ static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 use crate::builder::*;
 
 #[wasm_bindgen]
-pub struct SymbolTable(datalog::SymbolTable);
+#[derive(Clone, Debug)]
+pub struct SymbolTable(pub(crate) datalog::SymbolTable);
+
+#[wasm_bindgen]
+impl SymbolTable {
+    /// creates a custom base symbol table. It must contain "authority" and
+    /// "ambient", and no symbol can appear twice
+    #[wasm_bindgen(constructor)]
+    pub fn new(symbols: JsValue) -> Result<SymbolTable, JsValue> {
+        let symbols: Vec<String> = symbols.into_serde()
+            .map_err(|_| error::Error::InvalidParameter("symbols must be an array of strings".to_string()))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        SymbolTable::validated(symbols)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())
+    }
+
+    /// the symbol table used by default for new tokens
+    #[wasm_bindgen(js_name = default)]
+    pub fn default_table() -> SymbolTable {
+        SymbolTable(token::default_symbol_table())
+    }
+
+    /// adds the symbols that are not already in the table, at the end
+    #[wasm_bindgen]
+    pub fn extend(&mut self, symbols: JsValue) -> Result<(), JsValue> {
+        let symbols: Vec<String> = symbols.into_serde()
+            .map_err(|_| error::Error::InvalidParameter("symbols must be an array of strings".to_string()))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        for symbol in symbols.iter() {
+            self.0.insert(symbol);
+        }
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn contains(&self, symbol: &str) -> bool {
+        self.0.symbols.iter().any(|s| s == symbol)
+    }
+
+    /// returns the symbols, in the order of their ids
+    #[wasm_bindgen(js_name = toArray)]
+    pub fn to_array(&self) -> JsValue {
+        JsValue::from_serde(&self.0.symbols).unwrap()
+    }
+}
+
+impl SymbolTable {
+    pub(crate) fn validated(symbols: Vec<String>) -> Result<SymbolTable, error::Error> {
+        for (i, symbol) in symbols.iter().enumerate() {
+            if symbols[..i].contains(symbol) {
+                return Err(error::Error::InvalidParameter(format!("duplicate symbol \"{}\"", symbol)));
+            }
+        }
+
+        if !symbols.iter().any(|s| s == "authority") || !symbols.iter().any(|s| s == "ambient") {
+            return Err(error::Error::MissingSymbols);
+        }
+
+        Ok(SymbolTable(datalog::SymbolTable { symbols }))
+    }
+}
 
 #[wasm_bindgen()]
 pub struct BlockBuilder {
   facts: Vec<Fact>,
   rules: Vec<Rule>,
-  caveats: Vec<Rule>,
+  caveats: Vec<Caveat>,
+  strict: bool,
 }
 
 #[wasm_bindgen()]
@@ -34,6 +108,7 @@ impl BlockBuilder {
           facts: vec![],
           rules: vec![],
           caveats: vec![],
+          strict: false,
         }
     }
 
@@ -47,15 +122,148 @@ impl BlockBuilder {
         self.rules.push(rule);
     }
 
+    /// adds a caveat. Only the authority block and the verifier can use
+    /// caveats with multiple queries
     #[wasm_bindgen(js_name = addCaveat)]
-    pub fn add_caveat(&mut self, caveat: Rule) {
+    pub fn add_caveat(&mut self, caveat: Caveat) -> Result<(), JsValue> {
+        if caveat.queries.len() != 1 {
+            return Err(JsValue::from_serde(&error::Error::InvalidCaveat(caveat.to_string())).unwrap());
+        }
+
         self.caveats.push(caveat);
+        Ok(())
+    }
+
+    /// when enabled, `Biscuit.append` calls `validate` first
+    #[wasm_bindgen(js_name = setStrict)]
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    /// checks the block content before appending it to `biscuit`,
+    /// returning the errors the verifier would report
+    #[wasm_bindgen]
+    pub fn validate(&self, biscuit: &Biscuit) -> Result<(), JsValue> {
+        biscuit.blocks()
+            .and_then(|blocks| self.check(blocks.len() as u32))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())
+    }
+
+    /// returns the facts in the format of the `facts` of `toJSON`
+    #[wasm_bindgen(js_name = listFacts)]
+    pub fn list_facts(&self) -> js_sys::Array {
+        facts_to_js(&self.facts)
+    }
+
+    #[wasm_bindgen(js_name = removeFact)]
+    pub fn remove_fact(&mut self, index: usize) -> Result<Fact, JsValue> {
+        remove_at(&mut self.facts, index, "fact")
+    }
+
+    #[wasm_bindgen(js_name = removeRule)]
+    pub fn remove_rule(&mut self, index: usize) -> Result<Rule, JsValue> {
+        remove_at(&mut self.rules, index, "rule")
+    }
+
+    #[wasm_bindgen(js_name = removeCaveat)]
+    pub fn remove_caveat(&mut self, index: usize) -> Result<Caveat, JsValue> {
+        remove_at(&mut self.caveats, index, "caveat")
+    }
+
+    /// removes the facts, rules and caveats, keeping the settings
+    #[wasm_bindgen]
+    pub fn clear(&mut self) {
+        self.facts.clear();
+        self.rules.clear();
+        self.caveats.clear();
+    }
+
+    /// returns the draft as an object that can be stored and loaded with
+    /// `fromJSON`. Integers are decimal strings and dates are RFC 3339 strings
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> JsValue {
+        let data = BlockBuilderData {
+            version: DRAFT_FORMAT_VERSION,
+            block: BlockData::new(&self.facts, &self.rules, &self.caveats),
+            strict: self.strict,
+        };
+
+        JsValue::from_serde(&data).unwrap()
+    }
+
+    #[wasm_bindgen(js_name = fromJSON)]
+    pub fn from_json(value: JsValue) -> Result<BlockBuilder, JsValue> {
+        let data: BlockBuilderData = value.into_serde()
+            .map_err(|e| error::Error::Format(error::Format::DeserializationError(e.to_string())))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        check_draft_version(data.version)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        let (facts, rules, caveats) = data.block.into_parts()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        if let Some(caveat) = caveats.iter().find(|c| c.queries.len() != 1) {
+            return Err(JsValue::from_serde(&error::Error::InvalidCaveat(caveat.to_string())).unwrap());
+        }
+
+        Ok(BlockBuilder { facts, rules, caveats, strict: data.strict })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct BlockBuilderData {
+    version: u32,
+    #[serde(flatten)]
+    block: BlockData,
+    strict: bool,
+}
+
+impl BlockBuilder {
+    /// block facts and rules cannot use the `#authority` and `#ambient`
+    /// tags, and rules must bind all of their head variables
+    pub(crate) fn check(&self, block_id: u32) -> Result<(), error::Error> {
+        for fact in self.facts.iter() {
+            if has_tag(&fact.0, "authority") || has_tag(&fact.0, "ambient") {
+                return Err(error::Error::FailedLogic(error::Logic::InvalidBlockFact(
+                  error::InvalidBlockFact { block_id, fact: fact.to_string() })));
+            }
+        }
+
+        for rule in self.rules.iter().map(|r| &r.rule) {
+            let tagged = has_tag(&rule.0, "authority") || has_tag(&rule.0, "ambient");
+            if tagged || !unbound_variables(rule).is_empty() {
+                return Err(error::Error::FailedLogic(error::Logic::InvalidBlockRule(
+                  error::InvalidBlockRule { block_id, rule: print_rule(rule) })));
+            }
+        }
+
+        for query in self.caveats.iter().flat_map(|c| c.queries.iter()) {
+            if !unbound_variables(query).is_empty() {
+                return Err(error::Error::FailedLogic(error::Logic::InvalidBlockRule(
+                  error::InvalidBlockRule { block_id, rule: print_rule(query) })));
+            }
+        }
+
+        Ok(())
     }
 }
 
 #[wasm_bindgen]
 #[derive(Clone, Debug)]
-pub struct Biscuit(token::Biscuit);
+pub struct Biscuit {
+    token: token::Biscuit,
+    /// base symbol table the token was created or parsed with
+    symbols: datalog::SymbolTable,
+    /// bytes the token was parsed from, since the library does not keep
+    /// them. `None` for tokens created or appended to with this API
+    source: Option<Vec<u8>>,
+}
+
+/// facts, rules and caveats of a token block
+pub(crate) struct BuilderBlock {
+    pub facts: Vec<token::builder::Fact>,
+    pub rules: Vec<token::builder::Rule>,
+    pub caveats: Vec<Caveat>,
+}
 
 #[wasm_bindgen]
 impl Biscuit {
@@ -80,28 +288,57 @@ impl Biscuit {
             facts: vec![],
             rules: vec![],
             caveats: vec![],
+            auto_tag: false,
+            strict: false,
         }
     }
 
+    /// deserializes a token. `options` can set limits for untrusted tokens:
+    /// `{ maxBytes, maxBlocks, maxFactsPerBlock, maxSymbols, maxStringLength }`,
+    /// string lengths being counted in UTF-8 bytes. A token over any of them
+    /// is rejected with a `LimitExceeded` error
     #[wasm_bindgen]
-    pub fn from(slice: &[u8]) -> Result<Biscuit, JsValue> {
+    pub fn from(slice: &[u8], options: JsValue) -> Result<Biscuit, JsValue> {
+        limits::ParseLimits::from_js(options)
+            .and_then(|limits| limits.check(slice, false))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
         token::Biscuit::from(slice)
             .map_err(|e| { let e: error::Error = e.into(); e})
             .map_err(|e| JsValue::from_serde(&e).expect("biscuit from error"))
-            .map(Biscuit)
+            .map(|token| Biscuit { token, symbols: token::default_symbol_table(), source: Some(slice.to_vec()) })
     }
 
+    /// deserializes a token created with a custom base symbol table, with
+    /// the same `options` as `from`
+    #[wasm_bindgen(js_name = fromWithSymbols)]
+    pub fn from_with_symbols(slice: &[u8], symbols: &SymbolTable, options: JsValue) -> Result<Biscuit, JsValue> {
+        limits::ParseLimits::from_js(options)
+            .and_then(|limits| limits.check(slice, false))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        token::Biscuit::from_with_symbols(slice, symbols.0.clone())
+            .map_err(|e| { let e: error::Error = e.into(); e})
+            .map_err(|e| JsValue::from_serde(&e).expect("biscuit from error"))
+            .map(|token| Biscuit { token, symbols: symbols.0.clone(), source: Some(slice.to_vec()) })
+    }
+
+    /// deserializes a sealed token, with the same `options` as `from`
     #[wasm_bindgen(js_name = fromSealed)]
-    pub fn from_sealed(slice: &[u8], secret: &[u8]) -> Result<Biscuit, JsValue> {
+    pub fn from_sealed(slice: &[u8], secret: &[u8], options: JsValue) -> Result<Biscuit, JsValue> {
+        limits::ParseLimits::from_js(options)
+            .and_then(|limits| limits.check(slice, true))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
         token::Biscuit::from_sealed(slice, secret)
             .map_err(|e| { let e: error::Error = e.into(); e})
             .map_err(|e| JsValue::from_serde(&e).expect("biscuit from error"))
-            .map(Biscuit)
+            .map(|token| Biscuit { token, symbols: token::default_symbol_table(), source: Some(slice.to_vec()) })
     }
 
     #[wasm_bindgen(js_name = toVec)]
     pub fn to_vec(&self) -> Result<Vec<u8>, JsValue> {
-        match self.0.clone().container().as_ref() {
+        match self.token.clone().container().as_ref() {
             None => Err(JsValue::from_serde(&error::Error::InternalError).unwrap()),
             Some(c) => c.to_vec()
               .map_err(|e| { let e: error::Format = e.into(); error::Error::Format(e)})
@@ -109,6 +346,15 @@ impl Biscuit {
         }
     }
 
+    /// serializes the token sealed with `secret`: no blocks can be appended,
+    /// and it is deserialized with `fromSealed`
+    #[wasm_bindgen]
+    pub fn seal(&self, secret: &[u8]) -> Result<Vec<u8>, JsValue> {
+        self.token.seal(secret)
+            .map_err(|e| { let e: error::Error = e.into(); e})
+            .map_err(|e| JsValue::from_serde(&e).unwrap())
+    }
+
     #[wasm_bindgen(js_name = createBlock)]
     pub fn create_block(&self) -> BlockBuilder {
         BlockBuilder::new()
@@ -120,7 +366,58 @@ impl Biscuit {
         keypair: crypto::KeyPair,
         block_builder: BlockBuilder,
     ) -> Result<Biscuit, JsValue> {
-        let mut builder = self.0.create_block();
+        self.append_signed(&keypair, block_builder)
+    }
+
+    #[wasm_bindgen]
+    pub fn print(&self) -> String {
+      self.token.print()
+    }
+
+    /// symbol table of the token: the default symbols followed by the
+    /// symbols declared by each block
+    #[wasm_bindgen]
+    pub fn symbols(&self) -> Result<SymbolTable, JsValue> {
+        self.symbol_table()
+            .map(SymbolTable)
+            .map_err(|e| JsValue::from_serde(&e).unwrap())
+    }
+
+    /// SHA-256 hash of the serialized token, as an hexadecimal string. It
+    /// identifies the token in logs and caches without storing it
+    ///
+    /// for tokens deserialized with `from`, `fromWithSymbols` or `fromSealed`,
+    /// the hash covers the bytes they were parsed from. For tokens created
+    /// with a builder or `append`, it covers the result of `toVec`
+    #[wasm_bindgen]
+    pub fn fingerprint(&self) -> Result<String, JsValue> {
+        let data = self.serialized()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        Ok(Sha256::digest(&data).iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    /// returns true if both tokens have the same serialization, as defined
+    /// for `fingerprint`
+    #[wasm_bindgen]
+    pub fn equals(&self, other: &Biscuit) -> Result<bool, JsValue> {
+        self.serialized()
+            .and_then(|data| other.serialized().map(|other| data == other))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())
+    }
+}
+
+impl Biscuit {
+    pub(crate) fn append_signed(
+        &self,
+        keypair: &crypto::KeyPair,
+        block_builder: BlockBuilder,
+    ) -> Result<Biscuit, JsValue> {
+        if block_builder.strict {
+            block_builder.validate(self)?;
+        }
+
+        let mut builder = self.token.create_block();
 
         for fact in block_builder.facts {
           builder.add_fact(fact.into_fact());
@@ -130,21 +427,163 @@ impl Biscuit {
           builder.add_rule(rule.into_rule());
         }
 
-        for caveat in block_builder.caveats {
-          builder.add_caveat(caveat.into_rule());
+        for mut caveat in block_builder.caveats {
+          builder.add_caveat(caveat.queries.remove(0));
         }
 
         let block = builder.build();
 
         let mut rng = OsRng;
-        self.0.append(&mut rng, &keypair.0, block)
+        self.token.append(&mut rng, &keypair.0, block)
             .map_err(|e| { let e: error::Error = e.into(); e})
             .map_err(|e| JsValue::from_serde(&e).expect("error append"))
-            .map(Biscuit)
+            .map(|token| Biscuit { token, symbols: self.symbols.clone(), source: None })
     }
 
-    #[wasm_bindgen]
-    pub fn print(&self) -> String {
-      self.0.print()
+    /// serialized token: the bytes it was parsed from, or the container
+    pub(crate) fn serialized(&self) -> Result<Vec<u8>, error::Error> {
+        if let Some(data) = self.source.as_ref() {
+            return Ok(data.clone());
+        }
+
+        let container = self.token.container();
+        let container = container.as_ref().ok_or(error::Error::InternalError)?;
+        container.to_vec()
+            .map_err(|e| error::Error::Format(e.into()))
+    }
+
+    /// decodes the authority block and the following blocks from the serialized container
+    pub(crate) fn blocks(&self) -> Result<Vec<token::Block>, error::Error> {
+        let container = self.token.container();
+        let container = container.as_ref().ok_or(error::Error::InternalError)?;
+
+        let mut blocks = Vec::new();
+        for slice in std::iter::once(&container.authority).chain(container.blocks.iter()) {
+            let block = schema::Block::decode(&slice[..])
+                .map_err(|e| error::Error::Format(error::Format::BlockDeserializationError(format!("{:?}", e))))?;
+            let block = convert::proto_block_to_token_block(&block)
+                .map_err(|e| error::Error::Format(e.into()))?;
+            blocks.push(block);
+        }
+
+        Ok(blocks)
+    }
+
+    /// symbol table of the whole token: the base symbols followed by the
+    /// symbols declared by each block
+    pub(crate) fn symbol_table(&self) -> Result<datalog::SymbolTable, error::Error> {
+        let mut symbols = self.symbols.clone();
+        for block in self.blocks()? {
+            symbols.symbols.extend(block.symbols.symbols);
+        }
+
+        Ok(symbols)
+    }
+
+    /// content of each block, converted back to the builder types by
+    /// printing it with the token's symbol table and parsing the result.
+    /// The caveats with multiple queries of the authority block are restored
+    pub(crate) fn builder_blocks(&self) -> Result<Vec<BuilderBlock>, error::Error> {
+        let symbols = self.symbol_table()?;
+
+        self.blocks()?.iter()
+            .map(|block| {
+                let rules = block.rules.iter()
+                    .map(|r| symbols.print_rule(r).parse().map_err(|_| error::Error::ParseError))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let caveats = block.caveats.iter()
+                    .map(|c| symbols.print_rule(c).parse().map_err(|_| error::Error::ParseError))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let (rules, caveats) = if block.index == 0 {
+                    Caveat::raise(rules, caveats, "caveat_query_authority", "authority")
+                } else {
+                    (rules, caveats.into_iter().map(|c| Caveat { queries: vec![c] }).collect())
+                };
+
+                Ok(BuilderBlock {
+                    facts: block.facts.iter()
+                        .map(|f| symbols.print_fact(f).parse().map_err(|_| error::Error::ParseError))
+                        .collect::<Result<_, _>>()?,
+                    rules,
+                    caveats,
+                })
+            })
+            .collect()
+    }
+
+    /// replaces the text of the failed caveats of the authority block
+    /// generated for caveats with multiple queries by the caveats themselves
+    pub(crate) fn print_failed_caveats(&self, mut e: error::Error) -> error::Error {
+        if let error::Error::FailedLogic(error::Logic::FailedCaveats(ref mut failed)) = e {
+            let blocks = match self.builder_blocks() {
+                Ok(blocks) => blocks,
+                Err(_) => return e,
+            };
+
+            for f in failed.iter_mut() {
+                if let error::FailedCaveat::Block(ref mut b) = f {
+                    let caveat = blocks.get(b.block_id as usize).and_then(|block| block.caveats.get(b.caveat_id as usize));
+                    if let Some(caveat) = caveat.filter(|c| c.queries.len() > 1) {
+                        b.rule = caveat.to_string();
+                    }
+                }
+            }
+        }
+
+        e
+    }
+
+    /// fails if a block contains a `revocation_id` fact with one of the ids,
+    /// or generates one with its rules
+    pub(crate) fn check_revocation(&self, revoked: &[i64]) -> Result<(), error::Error> {
+        if revoked.is_empty() {
+            return Ok(());
+        }
+
+        let mut symbols = self.symbol_table()?;
+        let mut world = self.world()?;
+        world.run();
+
+        let id = token::builder::variable("id");
+        let deny = token::builder::Rule(
+            token::builder::Predicate { name: "revoked".to_string(), ids: vec![id.clone()] },
+            vec![token::builder::Predicate { name: "revocation_id".to_string(), ids: vec![id] }],
+            vec![token::builder::Constraint {
+                id: "id".to_string(),
+                kind: token::builder::ConstraintKind::Integer(
+                    token::builder::IntConstraint::In(revoked.iter().cloned().collect())),
+            }],
+        );
+
+        let mut found = world.query_rule(deny.convert(&mut symbols)).iter()
+            .filter_map(|f| match f.predicate.ids.first() {
+                Some(datalog::ID::Integer(i)) => Some(*i),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        found.sort();
+        found.dedup();
+
+        if found.is_empty() {
+            Ok(())
+        } else {
+            Err(error::Error::FailedLogic(error::Logic::Revoked(found)))
+        }
+    }
+
+    /// loads the facts and rules of every block in a datalog world, without running it
+    pub(crate) fn world(&self) -> Result<datalog::World, error::Error> {
+        let mut world = datalog::World::new();
+        for block in self.blocks()? {
+            for fact in block.facts {
+                world.add_fact(fact);
+            }
+
+            for rule in block.rules {
+                world.add_rule(rule);
+            }
+        }
+
+        Ok(world)
     }
 }
@@ -0,0 +1,112 @@
+use biscuit::datalog;
+use biscuit::format::{convert, schema};
+use prost::Message;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::error::{Error, Format, LimitExceeded};
+
+/// limits applied when deserializing untrusted tokens. A missing limit is
+/// not checked
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct ParseLimits {
+    max_bytes: Option<usize>,
+    max_blocks: Option<usize>,
+    max_facts_per_block: Option<usize>,
+    max_symbols: Option<usize>,
+    /// counted in UTF-8 bytes, like `maxBytes`, not in characters
+    max_string_length: Option<usize>,
+}
+
+fn check(limit: &str, max: Option<usize>, found: usize) -> Result<(), Error> {
+    match max {
+        Some(max) if found > max => Err(Error::Format(Format::LimitExceeded(LimitExceeded {
+            limit: limit.to_string(),
+            max,
+            found,
+        }))),
+        _ => Ok(()),
+    }
+}
+
+fn id_strings(ids: &[datalog::ID]) -> impl Iterator<Item = &String> {
+    ids.iter().filter_map(|id| match id {
+        datalog::ID::Str(s) => Some(s),
+        _ => None,
+    })
+}
+
+fn constraint_strings(constraint: &datalog::Constraint) -> Vec<&String> {
+    match &constraint.kind {
+        datalog::ConstraintKind::Str(datalog::StrConstraint::Prefix(s))
+        | datalog::ConstraintKind::Str(datalog::StrConstraint::Suffix(s))
+        | datalog::ConstraintKind::Str(datalog::StrConstraint::Equal(s))
+        | datalog::ConstraintKind::Str(datalog::StrConstraint::Regex(s)) => vec![s],
+        datalog::ConstraintKind::Str(datalog::StrConstraint::In(set))
+        | datalog::ConstraintKind::Str(datalog::StrConstraint::NotIn(set)) => set.iter().collect(),
+        _ => vec![],
+    }
+}
+
+fn rule_strings(rule: &datalog::Rule) -> Vec<&String> {
+    let datalog::Rule(head, body, constraints) = rule;
+    std::iter::once(head).chain(body.iter())
+        .flat_map(|p| id_strings(&p.ids))
+        .chain(constraints.iter().flat_map(constraint_strings))
+        .collect()
+}
+
+impl ParseLimits {
+    pub(crate) fn from_js(options: JsValue) -> Result<ParseLimits, Error> {
+        if options.is_undefined() || options.is_null() {
+            return Ok(ParseLimits::default());
+        }
+
+        options.into_serde()
+            .map_err(|e| Error::InvalidParameter(format!("invalid parse options: {}", e)))
+    }
+
+    /// checks the serialized token against the limits, before deserializing
+    /// it with the library
+    pub(crate) fn check(&self, slice: &[u8], sealed: bool) -> Result<(), Error> {
+        check("maxBytes", self.max_bytes, slice.len())?;
+
+        if self.max_blocks.is_none() && self.max_facts_per_block.is_none()
+            && self.max_symbols.is_none() && self.max_string_length.is_none() {
+            return Ok(());
+        }
+
+        let (authority, blocks) = if sealed {
+            schema::SealedBiscuit::decode(slice).map(|b| (b.authority, b.blocks))
+        } else {
+            schema::Biscuit::decode(slice).map(|b| (b.authority, b.blocks))
+        }.map_err(|e| Error::Format(Format::DeserializationError(format!("{:?}", e))))?;
+
+        check("maxBlocks", self.max_blocks, blocks.len() + 1)?;
+
+        let mut symbols = 0;
+        for slice in std::iter::once(&authority).chain(blocks.iter()) {
+            let block = schema::Block::decode(&slice[..])
+                .map_err(|e| Error::Format(Format::BlockDeserializationError(format!("{:?}", e))))?;
+            let block = convert::proto_block_to_token_block(&block)
+                .map_err(|e| Error::Format(e.into()))?;
+
+            check("maxFactsPerBlock", self.max_facts_per_block, block.facts.len())?;
+            symbols += block.symbols.symbols.len();
+            check("maxSymbols", self.max_symbols, symbols)?;
+
+            if self.max_string_length.is_some() {
+                let longest = block.symbols.symbols.iter()
+                    .chain(block.facts.iter().flat_map(|f| id_strings(&f.predicate.ids)))
+                    .chain(block.rules.iter().chain(block.caveats.iter()).flat_map(rule_strings))
+                    .map(|s| s.len())
+                    .max()
+                    .unwrap_or(0);
+                check("maxStringLength", self.max_string_length, longest)?;
+            }
+        }
+
+        Ok(())
+    }
+}
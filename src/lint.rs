@@ -0,0 +1,190 @@
+use biscuit::token::builder;
+use serde::{Serialize, Deserialize};
+use wasm_bindgen::prelude::*;
+
+use std::collections::HashSet;
+
+use crate::{Biscuit, BlockBuilder};
+use crate::builder::print_rule;
+
+/// warning reported by `lint`. Unlike errors, they do not prevent
+/// using the token
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Warning {
+    /// a caveat uses a predicate that no fact, rule or known ambient fact provides
+    UnknownPredicate { block_id: u32, caveat_id: u32, caveat: String, predicate: String },
+    /// a rule uses a predicate that no fact, rule or known ambient fact provides
+    UnknownRulePredicate { block_id: u32, rule: String, predicate: String },
+    /// a variable appears only once in a rule or caveat
+    SingleUseVariable { block_id: u32, rule: String, variable: String },
+    /// a constraint applies to a variable that does not appear in the body
+    UnboundConstraint { block_id: u32, rule: String, variable: String },
+}
+
+/// names used by a rule or caveat, independently of the symbol table
+struct Query {
+    text: String,
+    head: (String, Vec<String>),
+    body: Vec<(String, Vec<String>)>,
+    constraints: Vec<String>,
+}
+
+impl Query {
+    fn from_builder(rule: &builder::Rule) -> Self {
+        let builder::Rule(head, body, constraints) = rule;
+        let predicate = |p: &builder::Predicate| {
+            let variables = p.ids.iter()
+                .filter_map(|id| match id {
+                    builder::Atom::Variable(v) => Some(v.clone()),
+                    _ => None,
+                })
+                .collect();
+            (p.name.clone(), variables)
+        };
+
+        Query {
+            text: print_rule(rule),
+            head: predicate(head),
+            body: body.iter().map(predicate).collect(),
+            constraints: constraints.iter().map(|c| c.id.clone()).collect(),
+        }
+    }
+
+    fn variable_warnings(&self, block_id: u32, warnings: &mut Vec<Warning>) {
+        let bound: HashSet<&String> = self.body.iter().flat_map(|(_, v)| v.iter()).collect();
+
+        let mut seen = HashSet::new();
+        for variable in self.constraints.iter() {
+            if !bound.contains(variable) && seen.insert(variable) {
+                warnings.push(Warning::UnboundConstraint { block_id, rule: self.text.clone(), variable: variable.clone() });
+            }
+        }
+
+        let mut occurrences: Vec<&String> = self.head.1.iter()
+            .chain(self.body.iter().flat_map(|(_, v)| v.iter()))
+            .chain(self.constraints.iter())
+            .collect();
+        occurrences.sort();
+        for variable in occurrences.iter() {
+            if occurrences.iter().filter(|v| *v == variable).count() == 1 {
+                warnings.push(Warning::SingleUseVariable { block_id, rule: self.text.clone(), variable: (*variable).clone() });
+            }
+        }
+    }
+}
+
+struct LintBlock {
+    id: u32,
+    facts: Vec<String>,
+    rules: Vec<Query>,
+    caveats: Vec<(u32, Query)>,
+}
+
+impl LintBlock {
+    /// names of the facts provided by the block and of its rule heads
+    fn produced(&self) -> impl Iterator<Item = String> + '_ {
+        self.facts.iter().cloned().chain(self.rules.iter().map(|r| r.head.0.clone()))
+    }
+}
+
+/// each block is evaluated with the authority block, the verifier and its
+/// own facts and rules: `known` contains the predicate names provided by the
+/// authority block and the verifier, and a block cannot use the predicates
+/// of the other attenuation blocks
+fn lint(blocks: &[LintBlock], known: &HashSet<String>) -> Vec<Warning> {
+    let mut warnings = vec![];
+    for block in blocks.iter() {
+        let producible: HashSet<String> = known.iter().cloned().chain(block.produced()).collect();
+
+        for rule in block.rules.iter() {
+            for (name, _) in rule.body.iter() {
+                if !producible.contains(name) {
+                    warnings.push(Warning::UnknownRulePredicate {
+                        block_id: block.id,
+                        rule: rule.text.clone(),
+                        predicate: name.clone(),
+                    });
+                }
+            }
+        }
+
+        for (caveat_id, caveat) in block.caveats.iter() {
+            for (name, _) in caveat.body.iter() {
+                if !producible.contains(name) {
+                    warnings.push(Warning::UnknownPredicate {
+                        block_id: block.id,
+                        caveat_id: *caveat_id,
+                        caveat: caveat.text.clone(),
+                        predicate: name.clone(),
+                    });
+                }
+            }
+        }
+
+        for query in block.rules.iter().chain(block.caveats.iter().map(|(_, c)| c)) {
+            query.variable_warnings(block.id, &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+impl Biscuit {
+    fn lint_blocks(&self) -> Result<Vec<LintBlock>, crate::error::Error> {
+        Ok(self.builder_blocks()?.iter().enumerate().map(|(id, block)| LintBlock {
+            id: id as u32,
+            facts: block.facts.iter().map(|f| f.0.name.clone()).collect(),
+            rules: block.rules.iter().map(Query::from_builder).collect(),
+            caveats: block.caveats.iter().enumerate()
+                .flat_map(|(i, c)| c.queries.iter().map(move |q| (i as u32, Query::from_builder(q))))
+                .collect(),
+        }).collect())
+    }
+}
+
+impl BlockBuilder {
+    fn lint_block(&self, id: u32) -> LintBlock {
+        LintBlock {
+            id,
+            facts: self.facts.iter().map(|f| f.0.name.clone()).collect(),
+            rules: self.rules.iter().map(|r| Query::from_builder(&r.rule)).collect(),
+            caveats: self.caveats.iter().enumerate()
+                .flat_map(|(i, c)| c.queries.iter().map(move |q| (i as u32, Query::from_builder(q))))
+                .collect(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Biscuit {
+    /// reports suspicious caveats and rules. `known_ambient_predicates` lists
+    /// the names of the facts the verifier will provide, like `resource`
+    #[wasm_bindgen]
+    pub fn lint(&self, known_ambient_predicates: JsValue) -> Result<JsValue, JsValue> {
+        let mut known: HashSet<String> = known_ambient_predicates.into_serde()
+            .map_err(|e| JsValue::from_serde(&crate::error::Error::InvalidParameter(e.to_string())).unwrap())?;
+        let blocks = self.lint_blocks()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        known.extend(blocks.iter().take(1).flat_map(|b| b.produced()));
+
+        Ok(JsValue::from_serde(&lint(&blocks, &known)).unwrap())
+    }
+}
+
+#[wasm_bindgen]
+impl BlockBuilder {
+    /// reports suspicious caveats and rules of this block, as if it was
+    /// appended to `biscuit`. See `Biscuit.lint`
+    #[wasm_bindgen]
+    pub fn lint(&self, biscuit: &Biscuit, known_ambient_predicates: JsValue) -> Result<JsValue, JsValue> {
+        let mut known: HashSet<String> = known_ambient_predicates.into_serde()
+            .map_err(|e| JsValue::from_serde(&crate::error::Error::InvalidParameter(e.to_string())).unwrap())?;
+        let blocks = biscuit.lint_blocks()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        known.extend(blocks.iter().take(1).flat_map(|b| b.produced()));
+
+        let block = self.lint_block(blocks.len() as u32);
+        Ok(JsValue::from_serde(&lint(&[block], &known)).unwrap())
+    }
+}
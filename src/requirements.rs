@@ -0,0 +1,156 @@
+use biscuit::token::builder;
+use wasm_bindgen::prelude::*;
+
+use std::collections::HashMap;
+
+use crate::Biscuit;
+use crate::builder::{constraint_to_js, date_to_js, has_tag, print_rule, unix_seconds};
+
+/// ambient facts that can be summarized, with `name(#ambient, value)` as format
+const AMBIENT_PREDICATES: [&str; 3] = ["resource", "operation", "time"];
+
+enum Requirement {
+    Resource(builder::Constraint),
+    Operation(builder::Constraint),
+    Before(u64),
+    After(u64),
+}
+
+/// what the caveats depending only on `#ambient` facts require from the
+/// verifier. All of the constraints must be satisfied
+#[derive(Default)]
+struct Requirements {
+    resource: Vec<builder::Constraint>,
+    operation: Vec<builder::Constraint>,
+    before: Option<u64>,
+    after: Option<u64>,
+    residual: Vec<String>,
+}
+
+impl Requirements {
+    fn add(&mut self, requirement: Requirement) {
+        match requirement {
+            Requirement::Resource(c) => self.resource.push(c),
+            Requirement::Operation(c) => self.operation.push(c),
+            Requirement::Before(t) => self.before = Some(self.before.map_or(t, |b| b.min(t))),
+            Requirement::After(t) => self.after = Some(self.after.map_or(t, |a| a.max(t))),
+        }
+    }
+
+    fn to_js(&self) -> JsValue {
+        let constraints = |v: &[builder::Constraint]| -> JsValue {
+            v.iter().cloned().map(constraint_to_js).collect::<js_sys::Array>().into()
+        };
+        let date = |d: Option<u64>| d.map(date_to_js).unwrap_or(JsValue::NULL);
+
+        let time = js_sys::Object::new();
+        js_sys::Reflect::set(&time, &JsValue::from_str("after"), &date(self.after)).unwrap();
+        js_sys::Reflect::set(&time, &JsValue::from_str("before"), &date(self.before)).unwrap();
+
+        let residual: js_sys::Array = self.residual.iter().map(|s| JsValue::from_str(s)).collect();
+
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("resource"), &constraints(&self.resource)).unwrap();
+        js_sys::Reflect::set(&result, &JsValue::from_str("operation"), &constraints(&self.operation)).unwrap();
+        js_sys::Reflect::set(&result, &JsValue::from_str("time"), &time).unwrap();
+        js_sys::Reflect::set(&result, &JsValue::from_str("residual"), &residual).unwrap();
+        result.into()
+    }
+}
+
+fn constraint(name: &str, kind: builder::ConstraintKind) -> builder::Constraint {
+    builder::Constraint { id: name.to_string(), kind }
+}
+
+/// returns `None` if the caveat uses other predicates, joins variables,
+/// has constraints that cannot be expressed on a single ambient fact, or
+/// only requires an ambient fact to be present, without constraining it
+fn summarize(caveat: &builder::Rule) -> Option<Vec<Requirement>> {
+    let builder::Rule(_, body, constraints) = caveat;
+    let mut requirements = vec![];
+    let mut variables: HashMap<&String, &str> = HashMap::new();
+
+    for predicate in body.iter() {
+        let name = predicate.name.as_str();
+        if predicate.ids.len() != 2 || !AMBIENT_PREDICATES.contains(&name) {
+            return None;
+        }
+
+        match (name, &predicate.ids[1]) {
+            ("resource", builder::Atom::Str(s)) => requirements.push(Requirement::Resource(
+                constraint(name, builder::ConstraintKind::String(builder::StrConstraint::Equal(s.clone()))))),
+            ("operation", builder::Atom::Symbol(s)) => requirements.push(Requirement::Operation(
+                constraint(name, builder::ConstraintKind::Symbol(builder::SymbolConstraint::In(
+                    Some(s.clone()).into_iter().collect()))))),
+            (_, builder::Atom::Variable(v)) => {
+                if variables.insert(v, name).is_some() {
+                    return None;
+                }
+            },
+            _ => return None,
+        }
+    }
+
+    for c in constraints.iter() {
+        let requirement = match (variables.get(&c.id).copied(), &c.kind) {
+            (Some("resource"), builder::ConstraintKind::String(_)) =>
+                Requirement::Resource(constraint("resource", c.kind.clone())),
+            (Some("operation"), builder::ConstraintKind::Symbol(_)) =>
+                Requirement::Operation(constraint("operation", c.kind.clone())),
+            (Some("time"), builder::ConstraintKind::Date(builder::DateConstraint::Before(t))) =>
+                Requirement::Before(unix_seconds(*t)),
+            (Some("time"), builder::ConstraintKind::Date(builder::DateConstraint::After(t))) =>
+                Requirement::After(unix_seconds(*t)),
+            _ => return None,
+        };
+        requirements.push(requirement);
+    }
+
+    if variables.keys().any(|v| !constraints.iter().any(|c| &c.id == *v)) {
+        return None;
+    }
+
+    Some(requirements)
+}
+
+#[wasm_bindgen]
+impl Biscuit {
+    /// summarizes what the caveats depending only on `#ambient` facts
+    /// require, without verifying the token:
+    ///
+    /// ```text
+    /// {
+    ///   resource: [constraints on the resource],
+    ///   operation: [constraints on the operation],
+    ///   time: { after: Date | null, before: Date | null },
+    ///   residual: [caveats that could not be summarized, as datalog]
+    /// }
+    /// ```
+    ///
+    /// constraints use the JSON format, with `resource` or `operation` as id
+    #[wasm_bindgen]
+    pub fn requirements(&self) -> Result<JsValue, JsValue> {
+        let blocks = self.builder_blocks()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        let mut requirements = Requirements::default();
+        for caveat in blocks.iter().flat_map(|b| b.caveats.iter()) {
+            let ambient_only = caveat.queries.iter()
+                .all(|q| !q.1.is_empty() && q.1.iter().all(|p| has_tag(p, "ambient")));
+            if !ambient_only {
+                continue;
+            }
+
+            // alternative queries cannot be summarized as constraints that must all hold
+            match caveat.queries.as_slice() {
+                [query] => match summarize(query) {
+                    Some(r) => r.into_iter().for_each(|r| requirements.add(r)),
+                    None => requirements.residual.push(print_rule(query)),
+                },
+                _ => requirements.residual.push(caveat.to_string()),
+            }
+        }
+
+        Ok(requirements.to_js())
+    }
+}
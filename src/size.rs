@@ -0,0 +1,76 @@
+use biscuit::format::schema;
+use prost::Message;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::Biscuit;
+use crate::error::{Error, Format};
+
+#[derive(Serialize)]
+struct BlockSize {
+    /// size of the serialized block
+    bytes: usize,
+    /// part of `bytes` used by the symbols declared in the block
+    symbol_bytes: usize,
+    symbols: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SizeReport {
+    /// size of the serialized token
+    total: usize,
+    /// the authority block, then the following blocks
+    blocks: Vec<BlockSize>,
+    /// public keys, signature and container encoding
+    overhead: usize,
+}
+
+fn block_size(slice: &[u8]) -> Result<BlockSize, Error> {
+    let block = schema::Block::decode(slice)
+        .map_err(|e| Error::Format(Format::BlockDeserializationError(format!("{:?}", e))))?;
+
+    let mut without_symbols = block.clone();
+    without_symbols.symbols.clear();
+
+    Ok(BlockSize {
+        bytes: slice.len(),
+        symbol_bytes: block.encoded_len() - without_symbols.encoded_len(),
+        symbols: block.symbols,
+    })
+}
+
+#[wasm_bindgen]
+impl Biscuit {
+    /// returns the size of each part of the serialized token, in bytes:
+    ///
+    /// ```text
+    /// {
+    ///   total: number,
+    ///   blocks: [{ bytes: number, symbol_bytes: number, symbols: [string] }],
+    ///   overhead: number
+    /// }
+    /// ```
+    ///
+    /// sealed tokens do not keep their signed container, so they are
+    /// rejected with an `InvalidParameter` error
+    #[wasm_bindgen(js_name = sizeReport)]
+    pub fn size_report(&self) -> Result<JsValue, JsValue> {
+        let report = self.token.container().as_ref()
+            .ok_or_else(|| Error::InvalidParameter("size reports are not available for sealed tokens".to_string()))
+            .and_then(|container| {
+                let total = container.to_vec()
+                    .map_err(|e| Error::Format(e.into()))?
+                    .len();
+                let blocks = std::iter::once(&container.authority)
+                    .chain(container.blocks.iter())
+                    .map(|slice| block_size(slice))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let overhead = total - blocks.iter().map(|b| b.bytes).sum::<usize>();
+
+                Ok(SizeReport { total, blocks, overhead })
+            })
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        Ok(JsValue::from_serde(&report).unwrap())
+    }
+}
@@ -0,0 +1,121 @@
+use biscuit::crypto;
+use biscuit::token;
+use serde::{Serialize, Deserialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{Biscuit, BlockBuilder, SymbolTable};
+use crate::builder::{BiscuitBuilder, BlockData, RuleData};
+use crate::crypto::KeyPair;
+use crate::error::{Error, Format};
+
+/// description of a token, used by `Biscuit.fromSpec` and `Biscuit.toSpec`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Spec {
+    /// base symbol table, the default one if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symbols: Option<Vec<String>>,
+    authority: BlockData,
+    #[serde(default)]
+    blocks: Vec<BlockSpec>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct BlockSpec {
+    #[serde(flatten)]
+    block: BlockData,
+    /// private key signing the block, generated if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<Vec<u8>>,
+}
+
+fn key_pair(bytes: &[u8]) -> Result<KeyPair, Error> {
+    crypto::PrivateKey::from_bytes(bytes)
+        .map(|key| KeyPair(crypto::KeyPair::from(key)))
+        .ok_or_else(|| Error::InvalidParameter("invalid private key".to_string()))
+}
+
+#[wasm_bindgen]
+impl Biscuit {
+    /// creates a token and its attenuation blocks from a description:
+    ///
+    /// ```text
+    /// {
+    ///   symbols: [base symbol table, optional],
+    ///   authority: { facts: [], rules: [], caveats: [] },
+    ///   blocks: [{ facts: [], rules: [], caveats: [], key: [private key bytes, optional] }]
+    /// }
+    /// ```
+    ///
+    /// facts, rules and caveats use the format of the builders' `toJSON`.
+    /// Returns `{ biscuit, keys }`, with the key pair of each block
+    #[wasm_bindgen(js_name = fromSpec)]
+    pub fn from_spec(spec: JsValue, root_key: &KeyPair) -> Result<JsValue, JsValue> {
+        let spec: Spec = spec.into_serde()
+            .map_err(|e| Error::Format(Format::DeserializationError(e.to_string())))
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        let (facts, rules, caveats) = spec.authority.into_parts()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+        let symbols = match spec.symbols {
+            Some(symbols) => SymbolTable::validated(symbols)
+                .map_err(|e| JsValue::from_serde(&e).unwrap())?.0,
+            None => token::default_symbol_table(),
+        };
+        let builder = BiscuitBuilder {
+            symbols,
+            facts,
+            rules,
+            caveats,
+            auto_tag: false,
+            strict: false,
+        };
+        let mut biscuit = builder.build_signed(root_key)?;
+
+        let keys = js_sys::Array::new();
+        for block in spec.blocks {
+            let key = match block.key {
+                Some(bytes) => key_pair(&bytes).map_err(|e| JsValue::from_serde(&e).unwrap())?,
+                None => KeyPair::new(),
+            };
+
+            let (facts, rules, caveats) = block.block.into_parts()
+                .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+            let mut block_builder = BlockBuilder { facts, rules, caveats: vec![], strict: false };
+            for caveat in caveats {
+                block_builder.add_caveat(caveat)?;
+            }
+
+            biscuit = biscuit.append_signed(&key, block_builder)?;
+            keys.push(&key.into());
+        }
+
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("biscuit"), &biscuit.into()).unwrap();
+        js_sys::Reflect::set(&result, &JsValue::from_str("keys"), &keys).unwrap();
+        Ok(result.into())
+    }
+
+    /// describes the token in the format of `fromSpec`, without the keys
+    #[wasm_bindgen(js_name = toSpec)]
+    pub fn to_spec(&self) -> Result<JsValue, JsValue> {
+        let blocks = self.builder_blocks()
+            .map_err(|e| JsValue::from_serde(&e).unwrap())?;
+
+        let mut blocks = blocks.into_iter().map(|b| BlockData {
+            facts: b.facts.into_iter().map(|f| f.0.into()).collect(),
+            rules: b.rules.into_iter().map(RuleData::from).collect(),
+            caveats: b.caveats.into_iter().map(|c| c.queries.into_iter().map(RuleData::from).collect()).collect(),
+        });
+
+        let authority = blocks.next()
+            .ok_or_else(|| JsValue::from_serde(&Error::InternalError).unwrap())?;
+        let base = &self.symbols.symbols;
+        let spec = Spec {
+            symbols: if *base == token::default_symbol_table().symbols { None } else { Some(base.clone()) },
+            authority,
+            blocks: blocks.map(|block| BlockSpec { block, key: None }).collect(),
+        };
+
+        Ok(JsValue::from_serde(&spec).unwrap())
+    }
+}
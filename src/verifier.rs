@@ -1,17 +1,52 @@
-use crate::builder::{Fact, Rule, Predicate, Atom, Constraint, ConstraintKind, ConstraintData};
+use crate::builder::{Fact, Rule, RuleData, Caveat, Predicate, json_integer};
 use crate::Biscuit;
+use crate::compiled::{Compiled, CompiledVerifier};
+use crate::error::FailedCaveat;
 
 use biscuit::token::builder;
+use biscuit::datalog::{SymbolTable, World};
+use serde::{Serialize, Deserialize};
 
 use std::time::{Duration, SystemTime};
 
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// result of `Verifier.evaluateMatrix` for a resource and operation pair
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MatrixCell {
+    pub resource: String,
+    pub operation: String,
+    pub allowed: bool,
+    /// caveats that failed for this pair, empty if it is allowed
+    pub failed_caveats: Vec<FailedCaveat>,
+}
+
+/// version of the format written by `Verifier.toBytes` and `Verifier.toJSON`
+const VERIFIER_FORMAT_VERSION: u32 = 1;
+
+/// verifier content in a structured format, to load it without parsing datalog
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct VerifierData {
+    version: u32,
+    facts: Vec<Predicate>,
+    rules: Vec<RuleData>,
+    /// queries of each caveat
+    caveats: Vec<Vec<RuleData>>,
+    auto_tag: bool,
+    /// ids rejected by `revocationCheck`, as decimal strings
+    #[serde(default, serialize_with = "json_integer::serialize_vec", deserialize_with = "json_integer::deserialize_vec")]
+    revoked: Vec<i64>,
+}
 
 #[wasm_bindgen]
+#[derive(Clone)]
 pub struct Verifier {
     facts: Vec<builder::Fact>,
     rules: Vec<builder::Rule>,
-    caveats: Vec<builder::Rule>,
+    caveats: Vec<Caveat>,
+    auto_tag: bool,
+    revoked: Vec<i64>,
 }
 
 #[wasm_bindgen]
@@ -22,6 +57,8 @@ impl Verifier {
             facts: vec![],
             rules: vec![],
             caveats: vec![],
+            auto_tag: false,
+            revoked: vec![],
         }
     }
 
@@ -30,14 +67,21 @@ impl Verifier {
         self.facts.push(builder::Fact(fact.0));
     }
 
+    /// when enabled, facts that do not start with `#ambient` get it added
+    /// when verifying
+    #[wasm_bindgen(js_name = setAutoTag)]
+    pub fn set_auto_tag(&mut self, enabled: bool) {
+        self.auto_tag = enabled;
+    }
+
     #[wasm_bindgen(js_name = addRule)]
     pub fn add_rule(&mut self, rule_bind: Rule) {
         self.rules.push(rule_bind.into_rule());
     }
 
     #[wasm_bindgen(js_name = addCaveat)]
-    pub fn add_caveat(&mut self, caveat: Rule) {
-        self.caveats.push(caveat.into_rule());
+    pub fn add_caveat(&mut self, caveat: Caveat) {
+        self.caveats.push(caveat);
     }
 
     #[wasm_bindgen(js_name = addResource)]
@@ -53,60 +97,422 @@ impl Verifier {
             .push(builder::fact("operation", &[builder::s("ambient"), builder::s(operation)]));
     }
 
+    /// sets the current time from a `Date` object, an RFC 3339 string or a
+    /// number of milliseconds since the Unix epoch, as returned by `Date.now()`
     #[wasm_bindgen(js_name = setTime)]
-    pub fn set_time(&mut self, i: u64) {
-        self.facts.retain(|f| f.0.name != "time");
+    pub fn set_time(&mut self, date: JsValue) -> Result<(), JsValue> {
+        let i = crate::builder::js_to_date(&date)
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        self.set_unix_time(i);
+        Ok(())
+    }
 
-        let t = SystemTime::UNIX_EPOCH + Duration::new(i, 0);
-        self.facts
-            .push(builder::fact("time", &[builder::s("ambient"), builder::date(&t)]));
+    /// sets the current time from the JS clock
+    #[wasm_bindgen(js_name = setTimeNow)]
+    pub fn set_time_now(&mut self) {
+        self.set_unix_time((js_sys::Date::now() / 1000.0) as u64);
     }
 
+    /// rejects tokens containing a `revocation_id` fact with one of the ids,
+    /// given as BigInt, safe integer numbers or decimal strings. A token is
+    /// rejected as soon as one of its blocks matches, with a `Revoked` error
     #[wasm_bindgen(js_name = revocationCheck)]
-    pub fn revocation_check(&mut self, ids: &[i64]) {
-        let head_name =  "revocation_check".to_string();
-        let mut head_ids = vec![Atom { variable: Some("id".to_string()), ..Default::default() }];
-        let mut predicates = vec![Predicate {
-            name: "revocation_id".to_string(),
-            ids: vec![Atom { variable: Some("id".to_string()), ..Default::default() }] }];
-        let mut constraints = vec![Constraint {
-            id: "id".to_string(),
-            kind: ConstraintKind::Integer,
-            operation: "in".to_string(),
-            data: ConstraintData::IntegerSet(ids.iter().cloned().collect()),
-          }];
+    pub fn revocation_check(&mut self, ids: JsValue) -> Result<(), JsValue> {
+        let ids = ids.dyn_ref::<js_sys::Array>()
+            .ok_or_else(|| crate::error::Error::InvalidAtom("expected an array of revocation ids".to_string()))
+            .and_then(|a| a.iter().map(|i| crate::builder::js_to_integer(&i)).collect::<Result<Vec<_>, _>>())
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
 
-        let head_ids = head_ids.drain(..).map(|a| a.into_atom()).collect::<Vec<_>>();
-        let predicates = predicates.drain(..).map(|p| p.into_predicate()).collect::<Vec<_>>();
-        let constraints = constraints.drain(..).map(|p| p.into_constraint()).collect::<Vec<_>>();
+        self.revoked.extend(ids);
+        Ok(())
+    }
 
-        let caveat = Rule { rule: builder::constrained_rule(&head_name, &head_ids, &predicates, &constraints) };
+    #[wasm_bindgen]
+    pub fn verify(&self, root_key: &crate::crypto::PublicKey, biscuit: &Biscuit) -> Result<String, JsValue> {
+        let verifier = self.verified(root_key, biscuit)
+          .map_err(|e| e.to_js())?;
 
-        self.add_caveat(caveat);
+        Ok(verifier.print_world())
     }
 
+    /// parses and verifies each serialized token of the array, returning
+    /// for each token `null` if it is valid, or the error. `options` sets
+    /// the parse limits of `Biscuit.from`, checked for each token. Tokens
+    /// created with a custom base symbol table are parsed with `symbols`,
+    /// as in `Biscuit.fromWithSymbols`
+    #[wasm_bindgen(js_name = verifyMany)]
+    pub fn verify_many(
+        &self,
+        root_key: &crate::crypto::PublicKey,
+        tokens: JsValue,
+        options: JsValue,
+        symbols: Option<crate::SymbolTable>,
+    ) -> Result<js_sys::Array, JsValue> {
+        let limits = crate::limits::ParseLimits::from_js(options)
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        let tokens = tokens.dyn_ref::<js_sys::Array>()
+            .ok_or_else(|| crate::error::Error::InvalidParameter("expected an array of serialized tokens".to_string()))
+            .and_then(|a| a.iter()
+                .map(|t| t.dyn_into::<js_sys::Uint8Array>()
+                    .map_err(|_| crate::error::Error::InvalidParameter("expected an array of serialized tokens".to_string())))
+                .collect::<Result<Vec<_>, _>>())
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        let compiled = self.compiled()
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        let symbols = symbols.map(|s| s.0).unwrap_or_else(biscuit::token::default_symbol_table);
+
+        Ok(tokens.iter().map(|data| {
+            let data = data.to_vec();
+            let result = limits.check(&data, false)
+                .and_then(|()| biscuit::token::Biscuit::from_with_symbols(&data, symbols.clone())
+                    .map_err(|e| { let e: crate::error::Error = e.into(); e}))
+                .map(|token| Biscuit { token, symbols: symbols.clone(), source: Some(data) })
+                .and_then(|biscuit| compiled.verified(root_key, &biscuit, &[]).map(|_| ()));
+
+            match result {
+                Ok(()) => JsValue::NULL,
+                Err(e) => e.to_js(),
+            }
+        }).collect())
+    }
+
+    /// serializes the facts, rules, caveats, revocation ids and settings of
+    /// the verifier: a format version byte, followed by the `toJSON` content.
+    /// Parse limits are not part of the verifier, they are given to each call
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        let mut data = vec![VERIFIER_FORMAT_VERSION as u8];
+        serde_json::to_writer(&mut data, &self.data())
+            .map_err(|e| crate::error::Error::Format(crate::error::Format::SerializationError(e.to_string())))
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+
+        Ok(data)
+    }
+
+    /// loads a verifier serialized with `toBytes`
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(data: &[u8]) -> Result<Verifier, JsValue> {
+        match data.split_first() {
+            Some((version, data)) if *version as u32 == VERIFIER_FORMAT_VERSION => serde_json::from_slice(data)
+                .map_err(|e| crate::error::Error::Format(crate::error::Format::DeserializationError(e.to_string()))),
+            Some((version, _)) => Err(crate::error::Error::Format(crate::error::Format::DeserializationError(
+                format!("unsupported verifier format version {}", version)))),
+            None => Err(crate::error::Error::Format(crate::error::Format::DeserializationError(
+                "empty verifier data".to_string()))),
+        }
+        .and_then(Verifier::from_data)
+        .map_err(|e| JsValue::from_serde(&e).expect("error serde"))
+    }
+
+    /// returns the content of the verifier as an object, with integers
+    /// as decimal strings
+    #[wasm_bindgen(js_name = toJSON)]
+    pub fn to_json(&self) -> JsValue {
+        JsValue::from_serde(&self.data()).expect("error serde")
+    }
+
+    /// loads a verifier from the object returned by `toJSON`
+    #[wasm_bindgen(js_name = fromJSON)]
+    pub fn from_json(value: JsValue) -> Result<Verifier, JsValue> {
+        value.into_serde()
+            .map_err(|e| crate::error::Error::Format(crate::error::Format::DeserializationError(e.to_string())))
+            .and_then(Verifier::from_data)
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))
+    }
+
+    /// checks the facts and lowers the caveats once, to verify many tokens
+    /// with the same policy. Facts that change with each request can be
+    /// given to `CompiledVerifier.verify` instead
     #[wasm_bindgen]
-    pub fn verify(&self, root_key: &crate::crypto::PublicKey, biscuit: Biscuit) -> Result<String, JsValue> {
-        let mut verifier = biscuit.0.verify(root_key.0)
+    pub fn compile(&self) -> Result<CompiledVerifier, JsValue> {
+        self.compiled()
+            .map(CompiledVerifier)
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))
+    }
+
+    #[wasm_bindgen(js_name = worldToDot)]
+    pub fn world_to_dot(&self, root_key: &crate::crypto::PublicKey, biscuit: &Biscuit) -> Result<String, JsValue> {
+        biscuit.token.verify(root_key.0)
             .map_err(|e| { let e: crate::error::Error = e.into(); e})
             .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
 
-        for fact in self.facts.iter() {
-            verifier.add_fact(fact.clone());
+        let mut symbols = biscuit.symbol_table()
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        let mut world = biscuit.world()
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+
+        let mut dot = crate::dot::Dot::new("world");
+        biscuit.write_blocks(&mut dot, &symbols)
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+
+        let lowered = self.lowered_caveats();
+        let facts = self.ambient_facts().map(|f| f.convert(&mut symbols)).collect::<Vec<_>>();
+        let rules = self.rules.iter()
+            .chain(lowered.iter().flat_map(|(rules, _)| rules.iter()))
+            .map(|r| r.convert(&mut symbols))
+            .collect::<Vec<_>>();
+        let caveats = lowered.iter().map(|(_, c)| c.convert(&mut symbols)).collect::<Vec<_>>();
+        dot.cluster("verifier", "verifier", &symbols, &facts, &rules, &caveats);
+
+        self.load(&mut world, &mut symbols);
+        world.run();
+        dot.derived(&world, &symbols);
+
+        Ok(dot.finish())
+    }
+
+    /// checks each pair of `resources` and `operations` against the token,
+    /// replacing the facts from `addResource` and `addOperation`. `time`
+    /// replaces the time set with `setTime` if it is defined
+    ///
+    /// the verifier is compiled once, and each cell is verified by the token
+    /// verifier, so it gives the same result as `verify`. Returns an array with
+    /// one row per resource, each containing one
+    /// `{ resource, operation, allowed, failed_caveats }` cell per operation
+    #[wasm_bindgen(js_name = evaluateMatrix)]
+    pub fn evaluate_matrix(
+        &self,
+        root_key: &crate::crypto::PublicKey,
+        biscuit: &Biscuit,
+        resources: JsValue,
+        operations: JsValue,
+        time: JsValue,
+    ) -> Result<JsValue, JsValue> {
+        let resources: Vec<String> = resources.into_serde()
+            .map_err(|e| JsValue::from_serde(&crate::error::Error::InvalidParameter(e.to_string())).unwrap())?;
+        let operations: Vec<String> = operations.into_serde()
+            .map_err(|e| JsValue::from_serde(&crate::error::Error::InvalidParameter(e.to_string())).unwrap())?;
+
+        let mut verifier = self.clone();
+        verifier.facts.retain(|f| f.0.name != "resource" && f.0.name != "operation");
+        if !time.is_undefined() && !time.is_null() {
+            verifier.set_time(time)?;
+        }
+        let compiled = verifier.compiled()
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+
+        let mut rows = Vec::new();
+        for resource in resources.iter() {
+            let mut row = Vec::new();
+            for operation in operations.iter() {
+                let failed_caveats = compiled.failed_caveats(root_key, biscuit, &[
+                    builder::fact("resource", &[builder::s("ambient"), builder::string(resource)]),
+                    builder::fact("operation", &[builder::s("ambient"), builder::s(operation)]),
+                ]).map_err(|e| e.to_js())?;
+
+                row.push(MatrixCell {
+                    resource: resource.clone(),
+                    operation: operation.clone(),
+                    allowed: failed_caveats.is_empty(),
+                    failed_caveats,
+                });
+            }
+            rows.push(row);
+        }
+
+        Ok(JsValue::from_serde(&rows).unwrap())
+    }
+
+    /// finds when the token is valid, keeping the other facts of the verifier
+    /// and replacing the time set with `setTime`
+    ///
+    /// the result is a list of `{ after, before }` intervals, where `after`
+    /// and `before` are included, or `null` if the interval is unbounded.
+    /// It is empty if the token never verifies, and usually has one element
+    #[wasm_bindgen(js_name = validityWindow)]
+    pub fn validity_window(&self, root_key: &crate::crypto::PublicKey, biscuit: &Biscuit) -> Result<JsValue, JsValue> {
+        let mut verifier = self.clone();
+        verifier.facts.retain(|f| f.0.name != "time");
+        let compiled = verifier.compiled()
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        biscuit.token.verify(root_key.0)
+            .map_err(|e| { let e: crate::error::Error = e.into(); e})
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+        let blocks = biscuit.builder_blocks()
+            .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+
+        // verification only changes at the dates used in constraints:
+        // `<=` stops matching the second after, `>=` starts matching at the date
+        let rules = verifier.rules.iter()
+            .chain(verifier.caveats.iter().flat_map(|c| c.queries.iter()))
+            .chain(blocks.iter().flat_map(|b| b.rules.iter().chain(b.caveats.iter().flat_map(|c| c.queries.iter()))));
+        let mut candidates = vec![0];
+        for rule in rules {
+            for c in rule.2.iter() {
+                if let builder::ConstraintKind::Date(builder::DateConstraint::Before(t))
+                    | builder::ConstraintKind::Date(builder::DateConstraint::After(t)) = &c.kind {
+                    let t = crate::builder::unix_seconds(*t);
+                    candidates.push(t);
+                    candidates.push(t.saturating_add(1));
+                }
+            }
+        }
+        candidates.sort();
+        candidates.dedup();
+
+        let mut intervals: Vec<(u64, Option<u64>)> = Vec::new();
+        for (i, start) in candidates.iter().enumerate() {
+            let t = SystemTime::UNIX_EPOCH + Duration::from_secs(*start);
+            let valid = compiled.failed_caveats(root_key, biscuit, &[builder::fact("time", &[builder::s("ambient"), builder::date(&t)])])
+                .map_err(|e| e.to_js())?
+                .is_empty();
+            if !valid {
+                continue;
+            }
+
+            let end = candidates.get(i + 1).map(|next| next - 1);
+            match intervals.last_mut() {
+                Some((_, last_end)) if *last_end == start.checked_sub(1) => *last_end = end,
+                _ => intervals.push((*start, end)),
+            }
+        }
+
+        let date = |d: Option<u64>| d.map(crate::builder::date_to_js).unwrap_or(JsValue::NULL);
+        let result: js_sys::Array = intervals.iter().map(|(start, end)| {
+            let interval = js_sys::Object::new();
+            let after = if *start == 0 { None } else { Some(*start) };
+            js_sys::Reflect::set(&interval, &JsValue::from_str("after"), &date(after)).unwrap();
+            js_sys::Reflect::set(&interval, &JsValue::from_str("before"), &date(*end)).unwrap();
+            JsValue::from(interval)
+        }).collect();
+
+        Ok(result.into())
+    }
+}
+
+impl Verifier {
+    fn data(&self) -> VerifierData {
+        VerifierData {
+            version: VERIFIER_FORMAT_VERSION,
+            facts: self.facts.iter().map(|f| f.0.clone().into()).collect(),
+            rules: self.rules.iter().map(|r| r.clone().into()).collect(),
+            caveats: self.caveats.iter()
+                .map(|c| c.queries.iter().map(|q| q.clone().into()).collect())
+                .collect(),
+            auto_tag: self.auto_tag,
+            revoked: self.revoked.clone(),
+        }
+    }
+
+    fn from_data(data: VerifierData) -> Result<Verifier, crate::error::Error> {
+        if data.version != VERIFIER_FORMAT_VERSION {
+            return Err(crate::error::Error::Format(crate::error::Format::DeserializationError(
+                format!("unsupported verifier format version {}", data.version))));
+        }
+
+        Ok(Verifier {
+            facts: data.facts.into_iter()
+                .map(|p| p.try_into_predicate().map(builder::Fact))
+                .collect::<Result<_, _>>()?,
+            rules: data.rules.into_iter().map(|r| r.try_into_rule()).collect::<Result<_, _>>()?,
+            caveats: data.caveats.into_iter()
+                .map(|queries| queries.into_iter().map(|q| q.try_into_rule()).collect::<Result<_, _>>()
+                    .map(|queries| Caveat { queries }))
+                .collect::<Result<_, _>>()?,
+            auto_tag: data.auto_tag,
+            revoked: data.revoked,
+        })
+    }
+
+    /// verifies the token with the verifier's facts, rules and caveats
+    pub(crate) fn verified<'a>(&self, root_key: &crate::crypto::PublicKey, biscuit: &'a Biscuit)
+      -> Result<biscuit::token::verifier::Verifier<'a>, crate::error::Error> {
+        let mut verifier = biscuit.token.verify(root_key.0)
+            .map_err(|e| { let e: crate::error::Error = e.into(); e})?;
+        biscuit.check_revocation(&self.revoked)?;
+
+        for fact in self.ambient_facts() {
+            verifier.add_fact(fact);
         }
 
         for rule in self.rules.iter() {
             verifier.add_rule(rule.clone());
         }
 
-        for caveat in self.caveats.iter() {
-            verifier.add_caveat(caveat.clone());
+        for (rules, caveat) in self.lowered_caveats() {
+            for rule in rules {
+                verifier.add_rule(rule);
+            }
+            verifier.add_caveat(caveat);
         }
 
         verifier.verify()
           .map_err(|e| {let e: crate::error::Error = e.into(); e})
-          .map_err(|e| JsValue::from_serde(&e).expect("error serde"))?;
+          .map_err(|e| biscuit.print_failed_caveats(self.print_failed_caveats(e)))?;
 
-        Ok(verifier.print_world())
+        Ok(verifier)
+    }
+
+    /// replaces the rules generated for verifier caveats with the caveats
+    /// themselves in the error
+    pub(crate) fn print_failed_caveats(&self, mut e: crate::error::Error) -> crate::error::Error {
+        if let crate::error::Error::FailedLogic(crate::error::Logic::FailedCaveats(ref mut failed)) = e {
+            for f in failed.iter_mut() {
+                if let crate::error::FailedCaveat::Verifier(ref mut v) = f {
+                    if let Some(caveat) = self.caveats.get(v.caveat_id as usize) {
+                        v.rule = caveat.to_string();
+                    }
+                }
+            }
+        }
+
+        e
+    }
+
+    /// checks the facts and lowers the caveats, to give them to the token verifier
+    pub(crate) fn compiled(&self) -> Result<Compiled, crate::error::Error> {
+        let lowered = self.lowered_caveats();
+        let rules = self.rules.iter().cloned()
+            .chain(lowered.iter().flat_map(|(rules, _)| rules.iter().cloned()));
+        let caveats = lowered.iter()
+            .zip(self.caveats.iter())
+            .map(|((_, lowered), c)| (lowered.clone(), c.to_string()));
+
+        Compiled::new(self.ambient_facts(), rules, caveats, self.auto_tag, self.revoked.clone())
+    }
+
+    pub(crate) fn set_unix_time(&mut self, i: u64) {
+        self.facts.retain(|f| f.0.name != "time");
+
+        let t = SystemTime::UNIX_EPOCH + Duration::new(i, 0);
+        self.facts
+            .push(builder::fact("time", &[builder::s("ambient"), builder::date(&t)]));
+    }
+
+    /// facts provided by the verifier, with the `#ambient` tag added if
+    /// automatic tagging is enabled
+    pub(crate) fn ambient_facts(&self) -> impl Iterator<Item = builder::Fact> + '_ {
+        self.facts.iter().map(move |f| {
+            if self.auto_tag {
+                builder::Fact(crate::builder::tag_predicate(f.0.clone(), "ambient"))
+            } else {
+                f.clone()
+            }
+        })
+    }
+
+    /// adds the verifier's facts and rules to a world generated from a token
+    pub(crate) fn load(&self, world: &mut World, symbols: &mut SymbolTable) {
+        for fact in self.ambient_facts() {
+            world.add_fact(fact.convert(symbols));
+        }
+
+        for rule in self.rules.iter() {
+            world.add_rule(rule.convert(symbols));
+        }
+
+        for (rules, _) in self.lowered_caveats() {
+            for rule in rules {
+                world.add_rule(rule.convert(symbols));
+            }
+        }
+    }
+
+    /// converts the caveats to the rules and single rule caveats used by tokens
+    pub(crate) fn lowered_caveats(&self) -> Vec<(Vec<builder::Rule>, builder::Rule)> {
+        self.caveats.iter()
+            .enumerate()
+            .map(|(i, c)| c.lower(&format!("caveat_query_verifier_{}", i), "ambient"))
+            .collect()
     }
 }
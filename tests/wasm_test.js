@@ -1,6 +1,16 @@
 const wasm = require("wasm-bindgen-test.js")
 const assert = require("assert")
 
+// token with the authority fact `right(#authority, "file1", #read)`, followed
+// by `facts`, and the root key pair that signed it
+const tokenWithRight = (...facts) => {
+    let keypair = new wasm.KeyPair()
+    let builder = new wasm.Biscuit()
+    builder.addAuthorityFact(wasm.fact("right", [wasm.symbol("authority"), "file1", wasm.symbol("read")]))
+    facts.forEach(fact => builder.addAuthorityFact(fact))
+    return { keypair, biscuit: builder.build(keypair) }
+}
+
 exports.create_biscuit_with_authority_fact_and_verify_should_fail_on_caveat = () => {
     let keypair = new wasm.KeyPair()
     let public_key = keypair.publicKey()
@@ -52,9 +62,9 @@ exports.create_biscuit_with_authority_fact_and_verify_should_fail_on_caveat = ()
         }
         ]
     )*/
-    let rule = wasm.Rule.fromString("*right(#right) <- right(#authority, \"file2\", #write)")
+    let caveat = wasm.Caveat.fromString("*right(#right) <- right(#authority, \"file2\", #write)")
 
-    verifier.addCaveat(rule)
+    verifier.addCaveat(caveat)
 
     verifier.verify(public_key, biscuit2)
 };
@@ -93,7 +103,7 @@ exports.create_block_with_authority_fact_and_verify = () => {
         ]
     )
 
-    block2.addCaveat(rules)
+    block2.addCaveat(wasm.Caveat.fromRule(rules))
 
     let keypair2 = new wasm.KeyPair()
     let biscuit2 = biscuit1.append(keypair2, block2)
@@ -118,10 +128,925 @@ exports.create_block_with_authority_fact_and_verify = () => {
 
     let block3 = biscuit2.createBlock()
     console.table(block3)
-    block3.addCaveat(rule)
+    block3.addCaveat(wasm.Caveat.fromRule(rule))
     let keypair3 = new wasm.KeyPair()
     let biscuit3 = biscuit2.append(keypair3, block3)
 
     console.log(rule)
     console.log(biscuit3.print())
 };
+
+exports.biscuit_to_dot = () => {
+    let keypair = new wasm.KeyPair()
+    let builder = new wasm.Biscuit()
+
+    builder.addAuthorityFact(wasm.Fact.fromString("right(#authority, \"file1\", #read)"))
+    builder.addAuthorityRule(wasm.Rule.fromString("*readable($0) <- right(#authority, $0, #read)"))
+    let biscuit = builder.build(keypair)
+
+    let dot = biscuit.toDot()
+    console.log(dot)
+
+    assert.ok(dot.startsWith("digraph biscuit {"))
+    assert.ok(dot.includes("subgraph cluster_block_0"))
+    assert.ok(dot.includes("label=\"right(#authority, \\\"file1\\\", #read)\", shape=box"))
+    assert.ok(dot.includes("block_0_rule_0 -> derived_fact_0;"))
+};
+
+exports.fact_and_rule_to_string = () => {
+    let fact = wasm.fact("right", [
+        wasm.symbol("authority"),
+        wasm.string("file1"),
+        wasm.symbol("read")
+    ])
+    assert.equal(wasm.Fact.fromString(fact.toString()).toString(), fact.toString())
+    assert.deepStrictEqual(fact.toJSON(), {
+        name: "right",
+        ids: [{ symbol: "authority" }, { string: "file1" }, { symbol: "read" }]
+    })
+
+    let rule = wasm.Rule.fromString("*right(#right) <- right(#authority, \"file2\", #write)")
+    assert.equal(rule.toString(), "*right(#right) <- right(#authority, \"file2\", #write)")
+    assert.equal(rule.toJSON().head.name, "right")
+    assert.equal(rule.toJSON().body.length, 1)
+
+    // same shape as the builders' drafts
+    let block = new wasm.BlockBuilder()
+    block.addFact(wasm.Fact.fromString(fact.toString()))
+    block.addRule(wasm.Rule.fromString(rule.toString()))
+    assert.deepStrictEqual(block.toJSON().facts[0], fact.toJSON())
+    assert.deepStrictEqual(block.listFacts()[0], fact.toJSON())
+    assert.deepStrictEqual(block.toJSON().rules[0], rule.toJSON())
+};
+
+exports.rule_from_template = () => {
+    let input = "file1\", #write) <- right(#authority, \"file1"
+    let rule = wasm.Rule.fromTemplate(
+        "*can_read({res}) <- right(#authority, {res}, {op})",
+        { res: input, op: { symbol: "read" } })
+    let expected = wasm.rule(
+        "can_read",
+        [wasm.string(input)],
+        [{ name: "right", ids: [wasm.symbol("authority"), wasm.string(input), wasm.symbol("read")] }])
+    assert.equal(rule.toString(), expected.toString())
+
+    let fact = wasm.Fact.fromTemplate("resource(#ambient, {res})", { res: "/a/{b}" })
+    assert.equal(fact.toString(), "resource(#ambient, \"/a/{b}\")")
+
+    assert.throws(() => wasm.Fact.fromTemplate("resource(#ambient, {res})", {}))
+
+    let injected = "a\" or *x() <- y(\""
+    let caveat = wasm.Caveat.fromTemplate(
+        "*check({res}) <- resource(#ambient, {res}) or *check({res}) <- owner(#ambient, {user}, {res})",
+        { res: injected, user: { symbol: "alice" } })
+    let expectedCaveat = wasm.Caveat.fromRule(wasm.rule(
+        "check",
+        [wasm.string(injected)],
+        [{ name: "resource", ids: [wasm.symbol("ambient"), wasm.string(injected)] }]))
+    expectedCaveat.addQuery(wasm.rule(
+        "check",
+        [wasm.string(injected)],
+        [{ name: "owner", ids: [wasm.symbol("ambient"), wasm.symbol("alice"), wasm.string(injected)] }]))
+    assert.equal(caveat.toString(), expectedCaveat.toString())
+
+    assert.throws(() => wasm.Fact.fromString("right(#authority"), e => e.kind === "ParseError")
+    assert.throws(() => wasm.Rule.fromString("*right(#authority) <-"), e => e.kind === "ParseError")
+    assert.throws(() => wasm.Caveat.fromTemplate("*check({res}) <- resource(#ambient, {res})", {}),
+        e => e.kind === "InvalidParameter")
+};
+
+exports.native_js_atoms = () => {
+    let fact = wasm.fact("data", [wasm.symbol("authority"), "file1", 12, 9007199254740993n, new Date(1000)])
+    assert.equal(fact.toJSON().ids[1].string, "file1")
+    assert.strictEqual(fact.toJSON().ids[2].integer, "12")
+    assert.strictEqual(fact.toJSON().ids[3].integer, "9007199254740993")
+    assert.strictEqual(fact.toJSON().ids[4].date, "1970-01-01T00:00:01Z")
+    assert.equal(wasm.fact("data", fact.toJSON().ids).toString(), fact.toString())
+    assert.ok(JSON.stringify(fact.toJSON()).includes("\"9007199254740993\""))
+
+    let literal = wasm.fact("data", [123n, wasm.integer(456n)])
+    assert.equal(literal.toString(), "data(123, 456)")
+    assert.strictEqual(wasm.integer(456n).integer, 456n)
+    assert.strictEqual(wasm.integer("9007199254740993").integer, 9007199254740993n)
+    assert.equal(wasm.fact("data", [{ integer: 123n }]).toString(), "data(123)")
+
+    assert.throws(() => wasm.fact("data", [1.5]), e => e.kind === "InvalidAtom")
+    assert.throws(() => wasm.fact("data", [9007199254740993]), e => e.kind === "InvalidAtom")
+    assert.throws(() => wasm.fact("data", [18446744073709551616n]), e => e.kind === "InvalidAtom")
+    assert.throws(() => wasm.fact("data", [{ integer: 1, string: "1" }]), e => e.kind === "InvalidAtom")
+    assert.throws(() => wasm.fact("data", [true]), e => e.kind === "InvalidAtom")
+};
+
+exports.bigint_revocation_check = () => {
+    let { keypair, biscuit } = tokenWithRight()
+
+    let block = biscuit.createBlock()
+    block.addFact(wasm.fact("revocation_id", [wasm.integer(9007199254740993n)]))
+    let biscuit2 = biscuit.append(new wasm.KeyPair(), block)
+
+    block = biscuit2.createBlock()
+    block.addFact(wasm.fact("revocation_id", [2n]))
+    let biscuit3 = biscuit2.append(new wasm.KeyPair(), block)
+
+    let verifier = new wasm.Verifier()
+    verifier.revocationCheck([9007199254740992n, 1])
+    verifier.verify(keypair.publicKey(), biscuit)
+    verifier.verify(keypair.publicKey(), biscuit2)
+    verifier.verify(keypair.publicKey(), biscuit3)
+
+    // the first block is revoked, the second one is not
+    verifier.revocationCheck([9007199254740993n])
+    verifier.verify(keypair.publicKey(), biscuit)
+    assert.throws(() => verifier.verify(keypair.publicKey(), biscuit3),
+        e => e.kind === "FailedLogic" && e.data.kind === "Revoked" && e.data.data[0] === 9007199254740993n)
+    assert.throws(() => verifier.compile().verify(keypair.publicKey(), biscuit3),
+        e => e.kind === "FailedLogic" && e.data.kind === "Revoked")
+    assert.deepEqual(verifier.verifyMany(keypair.publicKey(), [biscuit.toVec(), biscuit3.toVec()]).map(r => r && r.data.kind),
+        [null, "Revoked"])
+
+    // revocation ids keep their precision when the verifier is serialized
+    let restored = wasm.Verifier.fromJSON(JSON.parse(JSON.stringify(verifier.toJSON())))
+    assert.throws(() => restored.verify(keypair.publicKey(), biscuit2), e => e.data.kind === "Revoked")
+    assert.throws(() => wasm.Verifier.fromJSON({ version: 1, facts: [], rules: [], caveats: [], auto_tag: false, revoked: [9007199254740993] }),
+        e => e.kind === "Format" && e.data.kind === "DeserializationError")
+};
+
+exports.date_atoms = () => {
+    let fromDate = wasm.fact("expiration", [wasm.date(new Date("2020-09-01T12:00:00Z"))])
+    let fromString = wasm.fact("expiration", [wasm.date("2020-09-01T14:00:00+02:00")])
+    let fromTimestamp = wasm.fact("expiration", [wasm.date(new Date("2020-09-01T12:00:00Z").getTime())])
+    let fromObject = wasm.fact("expiration", [{ date: 1598961600999 }])
+    let fromBigInt = wasm.fact("expiration", [wasm.date(1598961600000n)])
+    assert.equal(fromDate.toString(), fromString.toString())
+    assert.equal(fromDate.toString(), fromTimestamp.toString())
+    assert.equal(fromDate.toString(), fromObject.toString())
+    assert.equal(fromDate.toString(), fromBigInt.toString())
+    assert.equal(fromDate.toJSON().ids[0].date, "2020-09-01T12:00:00Z")
+    assert.equal(wasm.fact("expiration", fromDate.toJSON().ids).toString(), fromDate.toString())
+    assert.equal(wasm.date(Date.now()).date.getTime() % 1000, 0)
+    assert.throws(() => wasm.date("yesterday"), e => e.kind === "InvalidAtom")
+    assert.throws(() => wasm.date(-1000), e => e.kind === "InvalidAtom")
+
+    let keypair = new wasm.KeyPair()
+    let builder = new wasm.Biscuit()
+    builder.addAuthorityCaveat(wasm.Caveat.fromRule(wasm.constrained_rule(
+        "expiration",
+        [{ variable: "date" }],
+        [{ name: "time", ids: [wasm.symbol("ambient"), { variable: "date" }] }],
+        [{ id: "date", kind: "date", operation: "<=", data: "2020-09-01T12:00:00Z" }])))
+    let biscuit = builder.build(keypair)
+
+    assert.ok(/2020-09-01T12:00:00(\+00:00|Z)/.test(biscuit.print()))
+
+    let verifier = new wasm.Verifier()
+    verifier.setTime(new Date("2020-08-01T00:00:00Z"))
+    verifier.verify(keypair.publicKey(), biscuit)
+    verifier.setTime(Date.parse("2020-09-01T12:00:00.999Z"))
+    verifier.verify(keypair.publicKey(), biscuit)
+
+    verifier.setTimeNow()
+    assert.throws(() => verifier.verify(keypair.publicKey(), biscuit), e => e.kind === "FailedLogic")
+};
+
+exports.constraint_operators = () => {
+    let body = [{ name: "data", ids: [{ variable: "0" }] }]
+    for (let c of [
+        { id: "0", kind: "integer", operation: "!=", data: 1 },
+        { id: "0", kind: "string", operation: "regex", data: "^/a/.*" },
+        { id: "0", kind: "string", operation: "!=", data: "a" },
+        { id: "0", kind: "date", operation: "<", data: "2020-09-01T00:00:00Z" },
+        { id: "0", kind: "date", operation: ">", data: "2020-09-01T00:00:00Z" },
+        { id: "0", kind: "symbol", operation: "=", data: "read" },
+        { id: "0", kind: "symbol", operation: "not in", data: ["read", "write"] },
+    ]) {
+        wasm.constrained_rule("check", [{ variable: "0" }], body, [c])
+    }
+
+    let equal = wasm.constrained_rule("check", [{ variable: "0" }], body,
+        [{ id: "0", kind: "date", operation: "=", data: "2020-09-01T00:00:00Z" }])
+    assert.equal(equal.toJSON().constraints.length, 2)
+    assert.deepEqual(equal.toJSON().constraints.map(c => c.operation).sort(), ["<=", ">="])
+
+    assert.throws(
+        () => wasm.constrained_rule("check", [{ variable: "0" }], body, [{ id: "0", kind: "string", operation: "<", data: "a" }]),
+        e => e.kind === "InvalidConstraint")
+};
+
+exports.typed_constraints = () => {
+    let keypair = new wasm.KeyPair()
+    let builder = new wasm.Biscuit()
+    builder.addAuthorityCaveat(wasm.Caveat.fromRule(wasm.constrained_rule(
+        "check",
+        [{ variable: "res" }, { variable: "op" }],
+        [
+            { name: "resource", ids: [wasm.symbol("ambient"), { variable: "res" }] },
+            { name: "operation", ids: [wasm.symbol("ambient"), { variable: "op" }] },
+        ],
+        [
+            wasm.StrConstraint.prefix("res", "/a/"),
+            wasm.SymbolConstraint.in("op", ["read", "write"]),
+        ])))
+    let biscuit = builder.build(keypair)
+
+    let verifier = new wasm.Verifier()
+    verifier.addResource("/a/file1")
+    verifier.addOperation("read")
+    verifier.verify(keypair.publicKey(), biscuit)
+
+    verifier = new wasm.Verifier()
+    verifier.addResource("/a/file1")
+    verifier.addOperation("delete")
+    assert.throws(() => verifier.verify(keypair.publicKey(), biscuit), e => e.kind === "FailedLogic")
+
+    assert.strictEqual(wasm.IntConstraint.lessThan("0", 9007199254740993n).toJSON().data, "9007199254740993")
+    assert.ok(wasm.constrained_rule("check", [{ variable: "0" }], [{ name: "data", ids: [{ variable: "0" }] }],
+        [wasm.IntConstraint.lessThan("0", 9007199254740993n).toJSON()]).toString().includes("9007199254740993"))
+    assert.strictEqual(wasm.DateConstraint.before("0", "2020-09-01T00:00:00Z").toJSON().operation, "<=")
+};
+
+exports.rule_builder = () => {
+    let rule = new wasm.RuleBuilder("can_read")
+        .head([wasm.variable("res")])
+        .body("right", [wasm.symbol("authority"), wasm.variable("res"), wasm.symbol("read")])
+        .where(wasm.StrConstraint.prefix("res", "/a/"))
+        .build()
+    assert.equal(rule.toJSON().body[0].name, "right")
+    assert.equal(rule.toJSON().constraints[0].operation, "prefix")
+
+    assert.throws(
+        () => new wasm.RuleBuilder("can_read")
+            .head([wasm.variable("res")])
+            .body("right", [wasm.symbol("authority"), wasm.variable("file"), wasm.symbol("read")])
+            .build(),
+        e => e.kind === "UnboundVariable" && e.data === "res")
+};
+
+exports.caveat_with_multiple_queries = () => {
+    let keypair = new wasm.KeyPair()
+    let builder = new wasm.Biscuit()
+    builder.addAuthorityFact(wasm.Fact.fromString("owner(#authority, \"alice\", \"file1\")"))
+    builder.addAuthorityCaveat(wasm.Caveat.fromString(
+        "*admin($u) <- user(#ambient, $u), admin(#authority, $u) or " +
+        "*owner($u) <- user(#ambient, $u), resource(#ambient, $r), owner(#authority, $u, $r)"))
+    let biscuit = builder.build(keypair)
+
+    let verifier = new wasm.Verifier()
+    verifier.addFact(wasm.Fact.fromString("user(#ambient, \"alice\")"))
+    verifier.addResource("file1")
+    verifier.verify(keypair.publicKey(), biscuit)
+
+    verifier = new wasm.Verifier()
+    verifier.addFact(wasm.Fact.fromString("user(#ambient, \"alice\")"))
+    verifier.addResource("file2")
+    let authorityCaveat = "*admin($u) <- user(#ambient, $u), admin(#authority, $u) or " +
+        "*owner($u) <- user(#ambient, $u), resource(#ambient, $r), owner(#authority, $u, $r)"
+    let reportsCaveat = e => e.kind === "FailedLogic" &&
+        e.data.data[0].kind === "Block" && e.data.data[0].block_id === 0 && e.data.data[0].rule === authorityCaveat
+    assert.throws(() => verifier.verify(keypair.publicKey(), biscuit), reportsCaveat)
+    assert.throws(() => verifier.compile().verify(keypair.publicKey(), biscuit), reportsCaveat)
+
+    // the token stores helper rules, but the caveat is described as written
+    let spec = biscuit.toSpec()
+    assert.equal(spec.authority.rules.length, 0)
+    assert.equal(spec.authority.caveats.length, 1)
+    assert.deepEqual(spec.authority.caveats[0].map(q => q.head.name), ["admin", "owner"])
+    assert.equal(wasm.Biscuit.fromSpec(spec, keypair).biscuit.toSpec().authority.caveats[0].length, 2)
+
+    let caveat = wasm.Caveat.fromString("*a($0) <- a(#ambient, \" or \", $0) or *b($0) <- b(#ambient, $0)")
+    assert.equal(caveat.toString(), "*a($0) <- a(#ambient, \" or \", $0) or *b($0) <- b(#ambient, $0)")
+    assert.throws(() => biscuit.createBlock().addCaveat(caveat), e => e.kind === "InvalidCaveat")
+
+    verifier.addCaveat(caveat)
+    try {
+        verifier.verify(keypair.publicKey(), biscuit)
+        assert.fail("verification should fail")
+    } catch (e) {
+        let failed = e.data.data.find(c => c.kind === "Verifier")
+        assert.equal(failed.rule, caveat.toString())
+    }
+};
+
+exports.automatic_tagging = () => {
+    let keypair = new wasm.KeyPair()
+    let builder = new wasm.Biscuit()
+    builder.setAutoTag(true)
+    builder.addAuthorityFact(wasm.fact("right", ["file1", wasm.symbol("read")]))
+    builder.addAuthorityFact(wasm.fact("right", [wasm.symbol("authority"), "file2", wasm.symbol("read")]))
+    builder.addRight("file3", "read")
+    let biscuit = builder.build(keypair)
+
+    let verifier = new wasm.Verifier()
+    verifier.setAutoTag(true)
+    verifier.addFact(wasm.fact("resource", ["file1"]))
+    verifier.addOperation("read")
+    verifier.addCaveat(wasm.Caveat.fromString("*check($0) <- resource(#ambient, $0), operation(#ambient, #read), right(#authority, $0, #read)"))
+    verifier.verify(keypair.publicKey(), biscuit)
+
+    verifier = new wasm.Verifier()
+    verifier.addResource("file3")
+    verifier.addOperation("read")
+    verifier.addCaveat(wasm.Caveat.fromString("*check($0) <- resource(#ambient, $0), operation(#ambient, #read), right(#authority, $0, #read)"))
+    verifier.verify(keypair.publicKey(), biscuit)
+};
+
+exports.validate_builders = () => {
+    let keypair = new wasm.KeyPair()
+    let builder = new wasm.Biscuit()
+    builder.addAuthorityFact(wasm.fact("right", ["file1", wasm.symbol("read")]))
+    assert.throws(() => builder.validate(), e => e.data.kind === "InvalidAuthorityFact")
+    builder.setAutoTag(true)
+    builder.validate()
+    let biscuit = builder.build(keypair)
+
+    let unbound = new wasm.Biscuit()
+    unbound.addAuthorityRule(wasm.Rule.fromString("*can_read($0) <- right(#authority, $1, #read)"))
+    assert.throws(() => unbound.validate(),
+        e => e.kind === "FailedLogic" && e.data.kind === "InvalidBlockRule" && e.data.data.block_id === 0)
+    unbound.setStrict(true)
+    assert.throws(() => unbound.build(keypair), e => e.data.kind === "InvalidBlockRule")
+    assert.throws(() => unbound.print(keypair), e => e.data.kind === "InvalidBlockRule")
+
+    let block = biscuit.createBlock()
+    block.addFact(wasm.fact("right", [wasm.symbol("authority"), "file2", wasm.symbol("read")]))
+    assert.throws(
+        () => block.validate(biscuit),
+        e => e.kind === "FailedLogic" && e.data.kind === "InvalidBlockFact" && e.data.data.block_id === 1)
+
+    block = biscuit.createBlock()
+    block.addRule(wasm.Rule.fromString("*can_read($0) <- right(#authority, $1, #read)"))
+    block.setStrict(true)
+    assert.throws(() => biscuit.append(new wasm.KeyPair(), block), e => e.data.kind === "InvalidBlockRule")
+};
+
+exports.lint_caveats = () => {
+    let { keypair, biscuit } = tokenWithRight()
+
+    let block = biscuit.createBlock()
+    block.addCaveat(wasm.Caveat.fromString("*check($r) <- resouce(#ambient, $r), right(#authority, $r, #read)"))
+    block.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r), operation(#ambient, $op)"))
+
+    let warnings = block.lint(biscuit, ["resource", "operation", "time"])
+    assert.ok(warnings.some(w => w.kind === "UnknownPredicate" && w.predicate === "resouce" && w.caveat_id === 0))
+    assert.ok(warnings.some(w => w.kind === "SingleUseVariable" && w.block_id === 1))
+    assert.ok(!warnings.some(w => w.kind === "UnknownPredicate" && w.caveat_id === 1))
+
+    let biscuit2 = biscuit.append(new wasm.KeyPair(), block)
+    warnings = biscuit2.lint(["resource", "operation", "time"])
+    assert.ok(warnings.some(w => w.kind === "UnknownPredicate" && w.predicate === "resouce" && w.block_id === 1))
+
+    // a block can only use the facts provided by itself, the authority block and the verifier
+    let early = biscuit.createBlock()
+    early.addRule(wasm.Rule.fromString("*allowed($r) <- delegated($r)"))
+    early.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r), delegated($r)"))
+    let late = biscuit.createBlock()
+    late.addFact(wasm.fact("delegated", ["file1"]))
+    let biscuit3 = biscuit.append(new wasm.KeyPair(), early).append(new wasm.KeyPair(), late)
+    warnings = biscuit3.lint(["resource", "operation", "time"])
+    assert.ok(warnings.some(w => w.kind === "UnknownRulePredicate" && w.predicate === "delegated" && w.block_id === 1))
+    assert.ok(warnings.some(w => w.kind === "UnknownPredicate" && w.predicate === "delegated" && w.block_id === 1))
+
+    let after = biscuit3.createBlock()
+    after.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r), delegated($r)"))
+    warnings = after.lint(biscuit3, ["resource", "operation", "time"])
+    assert.ok(warnings.some(w => w.kind === "UnknownPredicate" && w.predicate === "delegated" && w.block_id === 3))
+    assert.ok(!warnings.some(w => w.kind === "UnknownPredicate" && w.predicate === "resource"))
+
+    let own = biscuit3.createBlock()
+    own.addFact(wasm.fact("delegated", ["file1"]))
+    own.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r), delegated($r)"))
+    warnings = own.lint(biscuit3, ["resource", "operation", "time"])
+    assert.ok(!warnings.some(w => w.kind === "UnknownPredicate"))
+};
+
+exports.token_requirements = () => {
+    let keypair = new wasm.KeyPair()
+    let builder = new wasm.Biscuit()
+    builder.addAuthorityFact(wasm.fact("right", [wasm.symbol("authority"), "/a/file1", wasm.symbol("read")]))
+    builder.addAuthorityCaveat(wasm.Caveat.fromRule(wasm.constrained_rule(
+        "check",
+        [{ variable: "res" }],
+        [{ name: "resource", ids: [wasm.symbol("ambient"), { variable: "res" }] }],
+        [wasm.StrConstraint.prefix("res", "/a/")])))
+    let biscuit = builder.build(keypair)
+
+    let expiration = new Date("2030-01-01T00:00:00Z")
+    let block = biscuit.createBlock()
+    block.addCaveat(wasm.Caveat.fromRule(wasm.constrained_rule(
+        "expiration",
+        [{ variable: "t" }],
+        [
+            { name: "time", ids: [wasm.symbol("ambient"), { variable: "t" }] },
+            { name: "operation", ids: [wasm.symbol("ambient"), wasm.symbol("read")] },
+        ],
+        [wasm.DateConstraint.before("t", expiration)])))
+    block.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r), right(#authority, $r, #read)"))
+    block.addCaveat(wasm.Caveat.fromString("*check($u) <- user(#ambient, $u)"))
+    block.addCaveat(wasm.Caveat.fromString("*check($t) <- time(#ambient, $t)"))
+    let biscuit2 = biscuit.append(new wasm.KeyPair(), block)
+
+    let requirements = biscuit2.requirements()
+    assert.equal(requirements.resource.length, 1)
+    assert.equal(requirements.resource[0].operation, "prefix")
+    assert.equal(requirements.resource[0].data, "/a/")
+    assert.deepEqual(requirements.operation[0].data, ["read"])
+    assert.equal(requirements.time.before.getTime(), expiration.getTime())
+    assert.equal(requirements.time.after, null)
+    assert.equal(requirements.residual.length, 2)
+    assert.ok(requirements.residual[0].includes("user(#ambient"))
+    assert.ok(requirements.residual[1].includes("time(#ambient, $t)"))
+};
+
+exports.authorization_matrix = () => {
+    let { keypair, biscuit } = tokenWithRight(
+        wasm.fact("right", [wasm.symbol("authority"), "file1", wasm.symbol("write")]),
+        wasm.fact("right", [wasm.symbol("authority"), "file2", wasm.symbol("read")]))
+
+    let block = biscuit.createBlock()
+    block.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r), operation(#ambient, $op), right(#authority, $r, $op)"))
+    let biscuit2 = biscuit.append(new wasm.KeyPair(), block)
+
+    let verifier = new wasm.Verifier()
+    verifier.addResource("file1")
+    let matrix = verifier.evaluateMatrix(keypair.publicKey(), biscuit2, ["file1", "file2"], ["read", "write"], new Date())
+
+    assert.equal(matrix.length, 2)
+    assert.deepEqual(matrix[0].map(c => c.allowed), [true, true])
+    assert.deepEqual(matrix[1].map(c => c.allowed), [true, false])
+    assert.equal(matrix[1][1].resource, "file2")
+    assert.equal(matrix[1][1].operation, "write")
+    assert.equal(matrix[1][1].failed_caveats[0].kind, "Block")
+    assert.equal(matrix[1][1].failed_caveats[0].block_id, 1)
+
+    // each cell gives the same result as a verification with its facts
+    let block2 = biscuit2.createBlock()
+    block2.addRule(wasm.Rule.fromString("*readable($r) <- resource(#ambient, $r), operation(#ambient, #read)"))
+    block2.addCaveat(wasm.Caveat.fromString("*check($r) <- readable($r)"))
+    let data = biscuit2.append(new wasm.KeyPair(), block2).toVec()
+
+    let time = new Date()
+    let resources = ["file1", "file2", "file3"]
+    let operations = ["read", "write"]
+    let matchesVerify = (data, policy) => {
+        let checker = new wasm.Verifier()
+        checker.addCaveat(wasm.Caveat.fromString(policy))
+        let result = checker.evaluateMatrix(keypair.publicKey(), wasm.Biscuit.from(data), resources, operations, time)
+
+        resources.forEach((resource, i) => operations.forEach((operation, j) => {
+            let single = new wasm.Verifier()
+            single.addCaveat(wasm.Caveat.fromString(policy))
+            single.addResource(resource)
+            single.addOperation(operation)
+            single.setTime(time)
+            let failed = []
+            try {
+                single.verify(keypair.publicKey(), wasm.Biscuit.from(data))
+            } catch (e) {
+                failed = e.data.data
+            }
+            assert.equal(result[i][j].allowed, failed.length === 0, resource + " " + operation)
+            assert.deepEqual(result[i][j].failed_caveats, failed, resource + " " + operation)
+        }))
+        return result
+    }
+
+    matrix = matchesVerify(data, "*allowed($r) <- resource(#ambient, $r), right(#authority, $r, #read)")
+    assert.deepEqual(matrix.map(row => row.map(c => c.allowed)), [[true, false], [true, false], [false, false]])
+
+    // verifier caveats that only a fact of an attenuation block satisfies
+    let delegation = biscuit.createBlock()
+    delegation.addFact(wasm.fact("delegated", ["file3"]))
+    let delegated = biscuit.append(new wasm.KeyPair(), delegation)
+    let delegatedPolicy = "*allowed($r) <- resource(#ambient, $r), delegated($r)"
+    matchesVerify(delegated.toVec(), delegatedPolicy)
+    let restriction = delegated.createBlock()
+    restriction.addCaveat(wasm.Caveat.fromString("*check($op) <- operation(#ambient, $op)"))
+    matchesVerify(delegated.append(new wasm.KeyPair(), restriction).toVec(), delegatedPolicy)
+
+    // tokens with a custom base table
+    let table = new wasm.SymbolTable(["read", "write", "operation", "resource", "right", "ambient", "authority"])
+    let custom = wasm.BiscuitBuilder.withSymbols(table)
+    custom.addAuthorityFact(wasm.fact("right", [wasm.symbol("authority"), "file1", wasm.symbol("read")]))
+    custom.addAuthorityCaveat(wasm.Caveat.fromString(
+        "*check($r, $op) <- resource(#ambient, $r), operation(#ambient, $op), right(#authority, $r, $op)"))
+    let customData = custom.build(keypair).toVec()
+    matrix = new wasm.Verifier().evaluateMatrix(keypair.publicKey(), wasm.Biscuit.fromWithSymbols(customData, table),
+        ["file1", "file2"], ["read", "write"])
+    assert.deepEqual(matrix.map(row => row.map(c => c.allowed)), [[true, false], [false, false]])
+};
+
+exports.validity_window = () => {
+    let { keypair, biscuit } = tokenWithRight()
+
+    let start = new Date("2025-01-01T00:00:00Z")
+    let expiration = new Date("2030-01-01T00:00:00Z")
+    let block = biscuit.createBlock()
+    block.addCaveat(wasm.Caveat.fromRule(wasm.constrained_rule(
+        "window",
+        [{ variable: "t" }],
+        [{ name: "time", ids: [wasm.symbol("ambient"), { variable: "t" }] }],
+        [wasm.DateConstraint.after("t", start), wasm.DateConstraint.before("t", expiration)])))
+    let biscuit2 = biscuit.append(new wasm.KeyPair(), block)
+
+    let verifier = new wasm.Verifier()
+    let window = verifier.validityWindow(keypair.publicKey(), biscuit2)
+    assert.equal(window.length, 1)
+    assert.equal(window[0].after.getTime(), start.getTime())
+    assert.equal(window[0].before.getTime(), expiration.getTime())
+
+    assert.deepEqual(verifier.validityWindow(keypair.publicKey(), biscuit), [{ after: null, before: null }])
+
+    verifier.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r)"))
+    assert.equal(verifier.validityWindow(keypair.publicKey(), biscuit2).length, 0)
+
+    // the constraints of every block are combined, as in verify
+    let earlier = new Date("2028-01-01T00:00:00Z")
+    let later = biscuit2.createBlock()
+    later.addCaveat(wasm.Caveat.fromRule(wasm.constrained_rule(
+        "expiration",
+        [{ variable: "t" }],
+        [{ name: "time", ids: [wasm.symbol("ambient"), { variable: "t" }] }],
+        [wasm.DateConstraint.before("t", earlier)])))
+    let biscuit3 = biscuit2.append(new wasm.KeyPair(), later)
+    window = new wasm.Verifier().validityWindow(keypair.publicKey(), biscuit3)
+    assert.equal(window.length, 1)
+    assert.equal(window[0].after.getTime(), start.getTime())
+    assert.equal(window[0].before.getTime(), earlier.getTime())
+
+    let verifies = time => {
+        let single = new wasm.Verifier()
+        single.setTime(time)
+        try {
+            single.verify(keypair.publicKey(), biscuit3)
+            return true
+        } catch (e) {
+            return false
+        }
+    }
+    assert.equal(verifies(start), true)
+    assert.equal(verifies(earlier), true)
+    assert.equal(verifies(start.getTime() - 1000), false)
+    assert.equal(verifies(earlier.getTime() + 1000), false)
+};
+
+exports.verify_many = () => {
+    let { keypair, biscuit } = tokenWithRight()
+
+    let block = biscuit.createBlock()
+    block.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r), right(#authority, $r, #write)"))
+    let biscuit2 = biscuit.append(new wasm.KeyPair(), block)
+
+    let verifier = new wasm.Verifier()
+    verifier.addResource("file1")
+    let results = verifier.verifyMany(keypair.publicKey(), [
+        biscuit.toVec(),
+        biscuit2.toVec(),
+        new Uint8Array([1, 2, 3]),
+    ])
+
+    assert.equal(results.length, 3)
+    assert.equal(results[0], null)
+    assert.equal(results[1].kind, "FailedLogic")
+    assert.equal(results[2].kind, "Format")
+
+    assert.throws(() => verifier.verifyMany(keypair.publicKey(), ["token"]), e => e.kind === "InvalidParameter")
+
+    // tokens with a custom base table
+    let symbols = ["read", "write", "resource", "right", "ambient", "authority"]
+    let custom = wasm.BiscuitBuilder.withSymbols(new wasm.SymbolTable(symbols))
+    custom.addAuthorityFact(wasm.fact("right", [wasm.symbol("authority"), "file1", wasm.symbol("read")]))
+    let customData = custom.build(keypair).toVec()
+    let customResults = verifier.verifyMany(keypair.publicKey(), [customData], undefined, new wasm.SymbolTable(symbols))
+    assert.deepEqual(customResults, [null])
+    assert.notEqual(verifier.verifyMany(keypair.publicKey(), [customData])[0], null)
+};
+
+exports.compiled_verifier = () => {
+    let { keypair, biscuit } = tokenWithRight()
+
+    let block = biscuit.createBlock()
+    block.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r), owner(#authority, $r)"))
+    let biscuit2 = biscuit.append(new wasm.KeyPair(), block)
+
+    let verifier = new wasm.Verifier()
+    verifier.addCaveat(wasm.Caveat.fromString(
+        "*allowed($r, $op) <- resource(#ambient, $r), operation(#ambient, $op), right(#authority, $r, $op)"))
+    let compiled = verifier.compile()
+
+    compiled.verify(keypair.publicKey(), biscuit, { resource: "file1", operation: "read", time: new Date() })
+    assert.throws(
+        () => compiled.verify(keypair.publicKey(), biscuit, { resource: "file1", operation: "write" }),
+        e => e.kind === "FailedLogic" && e.data.data[0].kind === "Verifier")
+    assert.throws(
+        () => compiled.verify(keypair.publicKey(), biscuit2, { resource: "file1", operation: "read" }),
+        e => e.kind === "FailedLogic" && e.data.data[0].kind === "Block" && e.data.data[0].block_id === 1)
+    assert.throws(
+        () => compiled.verify(keypair.publicKey(), biscuit, { resource: 1 }),
+        e => e.kind === "InvalidParameter")
+
+    // same results as the verifier on tokens with several blocks
+    let block2 = biscuit2.createBlock()
+    block2.addFact(wasm.fact("owner", ["file1"]))
+    let biscuit3 = biscuit2.append(new wasm.KeyPair(), block2)
+    let data = biscuit3.toVec()
+    for (let request of [{ resource: "file1", operation: "read" }, { resource: "file2", operation: "read" }]) {
+        let single = wasm.Verifier.fromJSON(verifier.toJSON())
+        single.addResource(request.resource)
+        single.addOperation(request.operation)
+        let expected = null
+        try {
+            single.verify(keypair.publicKey(), wasm.Biscuit.from(data))
+        } catch (e) {
+            expected = e
+        }
+        let found = null
+        try {
+            compiled.verify(keypair.publicKey(), wasm.Biscuit.from(data), request)
+        } catch (e) {
+            found = e
+        }
+        assert.deepEqual(found, expected)
+        assert.notEqual(found, null)
+    }
+};
+
+exports.serialize_verifier = () => {
+    let { keypair, biscuit: token } = tokenWithRight()
+
+    let block = token.createBlock()
+    block.addFact(wasm.fact("revocation_id", [1]))
+    let biscuit = token.append(new wasm.KeyPair(), block)
+
+    let verifier = new wasm.Verifier()
+    verifier.addResource("file1")
+    verifier.addOperation("read")
+    verifier.setTime(new Date("2025-01-01T00:00:00Z"))
+    verifier.addCaveat(wasm.Caveat.fromString(
+        "*admin($u) <- user(#ambient, $u) or " +
+        "*allowed($r) <- resource(#ambient, $r), operation(#ambient, #read), right(#authority, $r, #read)"))
+    verifier.revocationCheck([2, 3])
+
+    let restored = wasm.Verifier.fromBytes(verifier.toBytes())
+    restored.verify(keypair.publicKey(), biscuit)
+    assert.deepEqual(restored.toJSON(), verifier.toJSON())
+
+    let fromJSON = wasm.Verifier.fromJSON(JSON.parse(JSON.stringify(verifier)))
+    fromJSON.verify(keypair.publicKey(), biscuit)
+
+    fromJSON.revocationCheck([1])
+    assert.throws(() => wasm.Verifier.fromBytes(fromJSON.toBytes()).verify(keypair.publicKey(), biscuit),
+        e => e.kind === "FailedLogic" && e.data.kind === "Revoked")
+
+    assert.throws(() => wasm.Verifier.fromBytes(new Uint8Array([1, 2, 3])),
+        e => e.kind === "Format" && e.data.kind === "DeserializationError")
+
+    // the bytes start with the format version and keep the revocation ids
+    let revoking = new wasm.Verifier()
+    revoking.setAutoTag(true)
+    revoking.revocationCheck([1n, 9007199254740993n])
+    let bytes = revoking.toBytes()
+    assert.equal(bytes[0], 1)
+    let restoredRevoking = wasm.Verifier.fromBytes(bytes)
+    assert.deepEqual(restoredRevoking.toJSON().revoked, ["1", "9007199254740993"])
+    assert.equal(restoredRevoking.toJSON().auto_tag, true)
+    assert.throws(() => restoredRevoking.verify(keypair.publicKey(), biscuit),
+        e => e.kind === "FailedLogic" && e.data.kind === "Revoked" && e.data.data[0] === 1n)
+
+    bytes[0] = 2
+    assert.throws(() => wasm.Verifier.fromBytes(bytes),
+        e => e.kind === "Format" && e.data.kind === "DeserializationError")
+    assert.throws(() => wasm.Verifier.fromBytes(new Uint8Array([])),
+        e => e.kind === "Format" && e.data.kind === "DeserializationError")
+};
+
+exports.builder_drafts = () => {
+    let builder = new wasm.Biscuit()
+    builder.addAuthorityFact(wasm.fact("right", [wasm.symbol("authority"), "file1", wasm.symbol("read")]))
+    builder.addAuthorityFact(wasm.fact("right", [wasm.symbol("authority"), "file2", wasm.symbol("read")]))
+    builder.addAuthorityRule(wasm.Rule.fromString("*readable($r) <- right(#authority, $r, #read)"))
+    builder.addAuthorityCaveat(wasm.Caveat.fromString(
+        "*admin($u) <- user(#ambient, $u) or *readable($r) <- resource(#ambient, $r), readable($r)"))
+    builder.setAutoTag(true)
+
+    let restored = wasm.BiscuitBuilder.fromJSON(JSON.parse(JSON.stringify(builder)))
+    assert.deepEqual(restored.toJSON(), builder.toJSON())
+
+    let invalid = builder.toJSON()
+    invalid.symbols = invalid.symbols.filter(s => s !== "ambient")
+    assert.throws(() => wasm.BiscuitBuilder.fromJSON(invalid), e => e.kind === "MissingSymbols")
+    invalid = builder.toJSON()
+    invalid.symbols.push(invalid.symbols[0])
+    assert.throws(() => wasm.BiscuitBuilder.fromJSON(invalid), e => e.kind === "InvalidParameter")
+    assert.equal(restored.listFacts().length, 2)
+
+    assert.equal(restored.removeFact(0).toString(), "right(#authority, \"file1\", #read)")
+    assert.equal(restored.listFacts()[0].ids[1].string, "file2")
+    assert.throws(() => restored.removeRule(1), e => e.kind === "InvalidParameter")
+    restored.removeCaveat(0)
+    assert.equal(restored.toJSON().caveats.length, 0)
+    restored.build(new wasm.KeyPair())
+
+    restored.clear()
+    assert.equal(restored.listFacts().length, 0)
+    assert.equal(restored.toJSON().auto_tag, true)
+
+    let block = new wasm.BlockBuilder()
+    block.addFact(wasm.fact("owner", ["alice", "file1"]))
+    block.addCaveat(wasm.Caveat.fromString("*check($r) <- resource(#ambient, $r), owner(\"alice\", $r)"))
+    let draft = JSON.parse(JSON.stringify(block))
+    assert.deepEqual(wasm.BlockBuilder.fromJSON(draft).toJSON(), block.toJSON())
+
+    draft.caveats[0].push(draft.caveats[0][0])
+    assert.throws(() => wasm.BlockBuilder.fromJSON(draft), e => e.kind === "InvalidCaveat")
+    assert.throws(() => wasm.BlockBuilder.fromJSON({ version: 2 }), e => e.kind === "Format")
+
+    // integers above 2^53 keep their precision, and listFacts uses the toJSON format
+    let large = new wasm.BlockBuilder()
+    large.addFact(wasm.fact("revocation_id", [BigInt("9007199254740993")]))
+    large.addFact(wasm.fact("expiration", [new Date("2030-01-01T00:00:00Z")]))
+    draft = JSON.parse(JSON.stringify(large))
+    assert.equal(draft.facts[0].ids[0].integer, "9007199254740993")
+    assert.equal(wasm.BlockBuilder.fromJSON(draft).listFacts()[0].ids[0].integer, "9007199254740993")
+    assert.deepEqual(large.listFacts(), large.toJSON().facts)
+    assert.equal(large.listFacts()[1].ids[0].date, "2030-01-01T00:00:00Z")
+
+    draft.facts[0].ids[0].integer = 9007199254740993
+    assert.throws(() => wasm.BlockBuilder.fromJSON(draft), e => e.kind === "Format")
+};
+
+exports.token_spec = () => {
+    let root = new wasm.KeyPair()
+    let key = new Uint8Array(32)
+    new wasm.KeyPair().toBytes(key)
+
+    let right = (file) => ({ name: "right", ids: [{ symbol: "authority" }, { string: file }, { symbol: "read" }] })
+    let spec = {
+        authority: { facts: [right("file1"), right("file2")] },
+        blocks: [
+            {
+                caveats: [[{
+                    head: { name: "check", ids: [{ variable: "r" }] },
+                    body: [
+                        { name: "resource", ids: [{ symbol: "ambient" }, { variable: "r" }] },
+                        { name: "right", ids: [{ symbol: "authority" }, { variable: "r" }, { symbol: "read" }] },
+                    ],
+                    constraints: [],
+                }]],
+            },
+            { facts: [{ name: "revocation_id", ids: [{ integer: 1 }] }], key: Array.from(key) },
+        ],
+    }
+
+    let { biscuit, keys } = wasm.Biscuit.fromSpec(spec, root)
+    assert.equal(keys.length, 2)
+    let bytes = new Uint8Array(32)
+    keys[1].toBytes(bytes)
+    assert.deepEqual(bytes, key)
+
+    let verifier = new wasm.Verifier()
+    verifier.addResource("file2")
+    verifier.verify(root.publicKey(), biscuit)
+
+    let described = biscuit.toSpec()
+    assert.equal(described.authority.facts.length, 2)
+    assert.equal(described.blocks.length, 2)
+    assert.equal(described.blocks[0].caveats[0][0].head.name, "check")
+
+    let copy = wasm.Biscuit.fromSpec(described, root).biscuit
+    assert.deepEqual(copy.toSpec(), described)
+
+    spec.blocks[1].key = [1, 2, 3]
+    assert.throws(() => wasm.Biscuit.fromSpec(spec, root), e => e.kind === "InvalidParameter")
+    assert.throws(() => wasm.Biscuit.fromSpec({ blocks: [] }, root),
+        e => e.kind === "Format" && e.data.kind === "DeserializationError")
+};
+
+exports.symbol_tables = () => {
+    let table = wasm.SymbolTable.default()
+    assert.ok(table.contains("authority"))
+    assert.ok(table.contains("ambient"))
+
+    let defaults = table.toArray()
+    table.extend(["owner", "read", "authority"])
+    assert.deepEqual(table.toArray(), defaults.concat(["owner", "read"]))
+
+    let custom = new wasm.SymbolTable(["authority", "ambient", "resource", "operation", "right"])
+    assert.equal(custom.toArray().length, 5)
+    assert.throws(() => new wasm.SymbolTable(["authority", "resource"]), e => e.kind === "MissingSymbols")
+    assert.throws(() => new wasm.SymbolTable(["authority", "ambient", "ambient"]), e => e.kind === "InvalidParameter")
+    assert.throws(() => new wasm.BiscuitBuilder(["right"]), e => e.kind === "MissingSymbols")
+
+    let keypair = new wasm.KeyPair()
+    let builder = wasm.BiscuitBuilder.withSymbols(wasm.SymbolTable.default())
+    builder.addAuthorityFact(wasm.fact("right", [wasm.symbol("authority"), "file1", wasm.symbol("read")]))
+    let biscuit = builder.build(keypair)
+
+    let block = biscuit.createBlock()
+    block.addFact(wasm.fact("owner", ["alice", "file1"]))
+    biscuit = biscuit.append(new wasm.KeyPair(), block)
+
+    let symbols = biscuit.symbols()
+    assert.deepEqual(symbols.toArray().slice(0, defaults.length), defaults)
+    assert.ok(symbols.contains("read"))
+    assert.ok(symbols.contains("owner"))
+};
+
+exports.size_report = () => {
+    let { keypair, biscuit } = tokenWithRight()
+    let right = (file) => wasm.fact("right", [wasm.symbol("authority"), file, wasm.symbol("read")])
+
+    let report = biscuit.sizeReport()
+    assert.equal(report.total, biscuit.toVec().length)
+    assert.equal(report.blocks.length, 1)
+    assert.deepEqual(report.blocks[0].symbols, ["read"])
+    assert.ok(report.blocks[0].symbol_bytes > 0)
+    assert.equal(report.overhead, report.total - report.blocks[0].bytes)
+
+    let secret = new Uint8Array(32)
+    assert.throws(() => wasm.Biscuit.fromSealed(biscuit.seal(secret), secret).sizeReport(),
+        e => e.kind === "InvalidParameter")
+
+    let table = wasm.SymbolTable.default()
+    table.extend(["read"])
+    let tuned = wasm.BiscuitBuilder.withSymbols(table)
+    tuned.addAuthorityFact(right("file1"))
+    let compact = tuned.build(keypair)
+
+    let compactReport = compact.sizeReport()
+    assert.deepEqual(compactReport.blocks[0].symbols, [])
+    assert.equal(compactReport.blocks[0].symbol_bytes, 0)
+    assert.ok(compactReport.total < report.total)
+
+    let parsed = wasm.Biscuit.fromWithSymbols(compact.toVec(), table)
+    assert.ok(parsed.symbols().contains("read"))
+    assert.equal(parsed.toSpec().symbols.length, table.toArray().length)
+
+    let verifier = new wasm.Verifier()
+    verifier.addCaveat(wasm.Caveat.fromString("*check($r) <- right(#authority, $r, #read)"))
+    verifier.verify(keypair.publicKey(), parsed)
+};
+
+exports.parse_limits = () => {
+    let { keypair, biscuit } = tokenWithRight(
+        wasm.fact("right", [wasm.symbol("authority"), "file2", wasm.symbol("read")]))
+
+    let block = biscuit.createBlock()
+    block.addFact(wasm.fact("owner", ["alice", "a very long file name"]))
+    let data = biscuit.append(new wasm.KeyPair(), block).toVec()
+
+    wasm.Biscuit.from(data)
+    wasm.Biscuit.from(data, { maxBytes: data.length, maxBlocks: 2, maxFactsPerBlock: 2, maxSymbols: 2, maxStringLength: 21 })
+
+    let exceeded = (options, limit, found) => assert.throws(() => wasm.Biscuit.from(data, options),
+        e => e.kind === "Format" && e.data.kind === "LimitExceeded" && e.data.data.limit === limit && e.data.data.found === found)
+    exceeded({ maxBytes: 10 }, "maxBytes", data.length)
+    exceeded({ maxBlocks: 1 }, "maxBlocks", 2)
+    exceeded({ maxFactsPerBlock: 1 }, "maxFactsPerBlock", 2)
+    exceeded({ maxSymbols: 1 }, "maxSymbols", 2)
+    exceeded({ maxStringLength: 20 }, "maxStringLength", 21)
+
+    assert.throws(() => wasm.Biscuit.from(data, { maxBytes: "10" }), e => e.kind === "InvalidParameter")
+
+    // string lengths are counted in UTF-8 bytes
+    let accented = biscuit.createBlock()
+    accented.addFact(wasm.fact("owner", ["éléphant"]))
+    let accentedData = biscuit.append(new wasm.KeyPair(), accented).toVec()
+    assert.throws(() => wasm.Biscuit.from(accentedData, { maxStringLength: 8 }),
+        e => e.data.kind === "LimitExceeded" && e.data.data.found === 10)
+
+    assert.throws(() => wasm.Biscuit.fromWithSymbols(data, wasm.SymbolTable.default(), { maxBlocks: 1 }),
+        e => e.data.kind === "LimitExceeded" && e.data.data.limit === "maxBlocks")
+    let results = new wasm.Verifier().verifyMany(keypair.publicKey(), [data, biscuit.toVec()], { maxBlocks: 1 })
+    assert.equal(results[0].data.kind, "LimitExceeded")
+    assert.equal(results[1], null)
+};
+
+exports.token_fingerprint = () => {
+    let { keypair, biscuit } = tokenWithRight()
+    let biscuit2 = biscuit.append(new wasm.KeyPair(), biscuit.createBlock())
+
+    let fingerprint = biscuit.fingerprint()
+    assert.ok(/^[0-9a-f]{64}$/.test(fingerprint))
+
+    let parsed = wasm.Biscuit.from(biscuit.toVec())
+    assert.equal(parsed.fingerprint(), fingerprint)
+    assert.ok(parsed.equals(biscuit))
+    assert.notEqual(biscuit2.fingerprint(), fingerprint)
+    assert.ok(!biscuit2.equals(biscuit))
+
+    let secret = new Uint8Array([1, 2, 3, 4])
+    let sealed = wasm.Biscuit.fromSealed(biscuit.seal(secret), secret)
+    let sealedAgain = wasm.Biscuit.fromSealed(biscuit.seal(secret), secret)
+    assert.ok(/^[0-9a-f]{64}$/.test(sealed.fingerprint()))
+    assert.equal(sealed.fingerprint(), sealedAgain.fingerprint())
+    assert.ok(sealed.equals(sealedAgain))
+    assert.ok(!sealed.equals(biscuit))
+
+    // parsed tokens are hashed from the bytes they were parsed from
+    let sha256 = data => require("crypto").createHash("sha256").update(Buffer.from(data)).digest("hex")
+    let data = biscuit2.toVec()
+    assert.equal(wasm.Biscuit.from(data).fingerprint(), sha256(data))
+    assert.equal(wasm.Biscuit.fromWithSymbols(data, wasm.SymbolTable.default()).fingerprint(), sha256(data))
+    let sealedData = biscuit.seal(secret)
+    assert.equal(wasm.Biscuit.fromSealed(sealedData, secret).fingerprint(), sha256(sealedData))
+    assert.equal(biscuit2.fingerprint(), sha256(data))
+};
@@ -11,6 +11,81 @@ extern "C" {
 
     #[wasm_bindgen(catch)]
     fn create_block_with_authority_fact_and_verify() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn biscuit_to_dot() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn fact_and_rule_to_string() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn rule_from_template() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn native_js_atoms() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn bigint_revocation_check() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn date_atoms() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn constraint_operators() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn typed_constraints() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn rule_builder() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn caveat_with_multiple_queries() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn automatic_tagging() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn validate_builders() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn lint_caveats() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn token_requirements() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn authorization_matrix() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn validity_window() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn verify_many() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn compiled_verifier() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn serialize_verifier() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn builder_drafts() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn token_spec() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn symbol_tables() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn size_report() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn parse_limits() -> Result<(), JsValue>;
+
+    #[wasm_bindgen(catch)]
+    fn token_fingerprint() -> Result<(), JsValue>;
 }
 
 #[wasm_bindgen_test]
@@ -49,3 +124,178 @@ fn wasm_create_block_with_authority_fact_only_and_verify() {
 
     assert!(res.is_ok())
 }
+
+#[wasm_bindgen_test]
+fn wasm_biscuit_to_dot() {
+    if let Err(e) = biscuit_to_dot() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_fact_and_rule_to_string() {
+    if let Err(e) = fact_and_rule_to_string() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_rule_from_template() {
+    if let Err(e) = rule_from_template() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_native_js_atoms() {
+    if let Err(e) = native_js_atoms() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_bigint_revocation_check() {
+    if let Err(e) = bigint_revocation_check() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_date_atoms() {
+    if let Err(e) = date_atoms() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_constraint_operators() {
+    if let Err(e) = constraint_operators() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_typed_constraints() {
+    if let Err(e) = typed_constraints() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_rule_builder() {
+    if let Err(e) = rule_builder() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_caveat_with_multiple_queries() {
+    if let Err(e) = caveat_with_multiple_queries() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_automatic_tagging() {
+    if let Err(e) = automatic_tagging() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_validate_builders() {
+    if let Err(e) = validate_builders() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_lint_caveats() {
+    if let Err(e) = lint_caveats() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_token_requirements() {
+    if let Err(e) = token_requirements() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_authorization_matrix() {
+    if let Err(e) = authorization_matrix() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_validity_window() {
+    if let Err(e) = validity_window() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_verify_many() {
+    if let Err(e) = verify_many() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_compiled_verifier() {
+    if let Err(e) = compiled_verifier() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_serialize_verifier() {
+    if let Err(e) = serialize_verifier() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_builder_drafts() {
+    if let Err(e) = builder_drafts() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_token_spec() {
+    if let Err(e) = token_spec() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_symbol_tables() {
+    if let Err(e) = symbol_tables() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_size_report() {
+    if let Err(e) = size_report() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_parse_limits() {
+    if let Err(e) = parse_limits() {
+        panic!("{:#?}", e)
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_token_fingerprint() {
+    if let Err(e) = token_fingerprint() {
+        panic!("{:#?}", e)
+    }
+}